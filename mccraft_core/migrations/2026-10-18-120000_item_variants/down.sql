@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX mccraft.item_base_id;
+ALTER TABLE mccraft.items DROP COLUMN nbt_hash;
+ALTER TABLE mccraft.items DROP COLUMN meta;
+ALTER TABLE mccraft.items DROP COLUMN base_id;
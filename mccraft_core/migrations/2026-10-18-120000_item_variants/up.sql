@@ -0,0 +1,27 @@
+-- The JEI exporter encodes the damage value (and, for items that carry one,
+-- the NBT tag) into the item type string, so `minecraft:wool:3` and
+-- `minecraft:wool:14` are different items that share a base ID. Split those
+-- pieces out so variants can be grouped together.
+ALTER TABLE mccraft.items ADD COLUMN base_id TEXT;
+ALTER TABLE mccraft.items ADD COLUMN meta INTEGER;
+ALTER TABLE mccraft.items ADD COLUMN nbt_hash TEXT;
+
+-- Backfill what we can from the existing IDs, following the same rules as
+-- `MinecraftId::parse`: an ID is only split if its third `:`-separated piece is
+-- a number that fits in an INTEGER. Anything else (fluids, mostly) keeps its
+-- whole ID as its base ID. The NBT hash is left for the importer to fill in on
+-- the next run, and the importer's upsert overwrites all three columns, so any
+-- row this gets wrong is corrected then.
+UPDATE mccraft.items SET
+  base_id = substring(minecraft_id from '^([^:]*:[^:]*):'),
+  meta = substring(minecraft_id from '^[^:]*:[^:]*:([-+]?[0-9]+)(:|$)')::NUMERIC
+WHERE ty = 'item'
+  AND minecraft_id ~ '^[^:]*:[^:]*:[-+]?[0-9]+(:|$)'
+  AND substring(minecraft_id from '^[^:]*:[^:]*:([-+]?[0-9]+)(:|$)')::NUMERIC
+    BETWEEN -2147483648 AND 2147483647;
+UPDATE mccraft.items SET base_id = minecraft_id WHERE base_id IS NULL;
+
+ALTER TABLE mccraft.items ALTER COLUMN base_id SET NOT NULL;
+
+-- "What variants does this item have?"
+CREATE INDEX item_base_id ON mccraft.items USING hash (base_id);
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE mccraft.input_slots DROP COLUMN ingredient_group;
+DROP INDEX mccraft.ingredient_group_member_item;
+DROP INDEX mccraft.ingredient_group_member_group;
+DROP TABLE mccraft.ingredient_group_members;
+DROP TABLE mccraft.ingredient_groups;
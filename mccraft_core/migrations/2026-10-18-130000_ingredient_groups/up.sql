@@ -0,0 +1,37 @@
+-- Many recipes accept the same large set of alternatives in a slot (any of the
+-- 16 planks, every mod's copper ingot, ...). Rather than storing one
+-- crafting_components row per alternative per recipe, slots can point at a
+-- shared ingredient group instead.
+--
+--   | input_slots      |                | ingredient_groups |
+--   |  for_recipe      |  --many to 1-> |  human_name       | <-\
+--   |  ingredient_group|                |  signature        |   |
+--                                                               |
+--                      | ingredient_group_members |--many to 1--/
+--                      |  ingredient_group        |
+--                      |  item                    |
+--                      |  quantity                |
+--
+-- A slot either has an ingredient group, or a list of crafting_components.
+
+CREATE TABLE mccraft.ingredient_groups (
+  id SERIAL PRIMARY KEY,
+  human_name TEXT NOT NULL,
+  -- Canonical description of the group members, so that re-imports find the
+  -- existing group instead of creating a new one
+  signature TEXT NOT NULL
+);
+
+ALTER TABLE mccraft.ingredient_groups ADD CONSTRAINT ingredient_group_signature_unique UNIQUE (signature);
+
+CREATE TABLE mccraft.ingredient_group_members (
+  id SERIAL PRIMARY KEY,
+  ingredient_group INTEGER REFERENCES mccraft.ingredient_groups(id) NOT NULL,
+  item INTEGER REFERENCES mccraft.items(id) NOT NULL,
+  quantity INTEGER NOT NULL
+);
+
+CREATE INDEX ingredient_group_member_group ON mccraft.ingredient_group_members USING hash (ingredient_group);
+CREATE INDEX ingredient_group_member_item ON mccraft.ingredient_group_members USING hash (item);
+
+ALTER TABLE mccraft.input_slots ADD COLUMN ingredient_group INTEGER REFERENCES mccraft.ingredient_groups(id);
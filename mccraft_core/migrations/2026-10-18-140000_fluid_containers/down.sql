@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX mccraft.fluid_container_fluid;
+DROP TABLE mccraft.fluid_containers;
@@ -0,0 +1,15 @@
+-- A recipe that takes a Water Bucket and one that takes 1000 mB of water are
+-- really asking for the same thing. This table records which items are filled
+-- containers, what they hold, and what is left over once they are emptied, so
+-- that calculations can treat them as fluid + empty container.
+CREATE TABLE mccraft.fluid_containers (
+  id SERIAL PRIMARY KEY,
+  filled_item INTEGER REFERENCES mccraft.items(id) NOT NULL,
+  fluid INTEGER REFERENCES mccraft.items(id) NOT NULL,
+  amount INTEGER NOT NULL,
+  -- Some containers are consumed when emptied
+  empty_item INTEGER REFERENCES mccraft.items(id)
+);
+
+ALTER TABLE mccraft.fluid_containers ADD CONSTRAINT fluid_container_filled_unique UNIQUE (filled_item);
+CREATE INDEX fluid_container_fluid ON mccraft.fluid_containers USING hash (fluid);
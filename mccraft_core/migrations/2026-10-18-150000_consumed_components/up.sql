@@ -0,0 +1,4 @@
+-- Some recipe inputs aren't used up by the recipe: tools that take damage in a
+-- crafting grid, casts in a smeltery, catalysts in a reactor and so on. Those
+-- only need to be made once, no matter how many times the recipe is performed.
+ALTER TABLE mccraft.crafting_components ADD COLUMN consumed BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX mccraft.machine_catalyst_item;
+DROP INDEX mccraft.machine_catalyst_machine;
+DROP TABLE mccraft.machine_catalysts;
@@ -0,0 +1,12 @@
+-- The blocks that can perform the recipes of a machine (JEI calls these recipe
+-- catalysts). A category can have several, e.g. every furnace-like block can
+-- smelt.
+CREATE TABLE mccraft.machine_catalysts (
+  id SERIAL PRIMARY KEY,
+  machine INTEGER REFERENCES mccraft.machines(id) NOT NULL,
+  item INTEGER REFERENCES mccraft.items(id) NOT NULL
+);
+
+ALTER TABLE mccraft.machine_catalysts ADD CONSTRAINT machine_catalyst_unique UNIQUE (machine, item);
+CREATE INDEX machine_catalyst_machine ON mccraft.machine_catalysts USING hash (machine);
+CREATE INDEX machine_catalyst_item ON mccraft.machine_catalysts USING hash (item);
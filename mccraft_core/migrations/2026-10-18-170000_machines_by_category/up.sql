@@ -0,0 +1,13 @@
+-- Machines used to be identified by their background texture, which collapses
+-- categories that share a texture into one machine. They are now identified by
+-- their JEI category, and the texture becomes a plain attribute.
+ALTER TABLE mccraft.machines ADD COLUMN bg_texture TEXT;
+UPDATE mccraft.machines SET bg_texture = minecraft_id;
+ALTER TABLE mccraft.machines ALTER COLUMN bg_texture SET NOT NULL;
+
+-- We don't know which categories were merged into each existing row, so the
+-- rows themselves can't be split here. Instead, the next import creates a
+-- machine for every category and moves the recipes it knows over to them. It
+-- then deletes the recipes left on the old texture-keyed rows (they predate
+-- content hashes, and the import has just replaced them) and removes any old
+-- row that ends up with no recipes.
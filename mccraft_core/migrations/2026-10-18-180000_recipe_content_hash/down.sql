@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX mccraft.recipe_content_hash;
+ALTER TABLE mccraft.recipes DROP COLUMN content_hash;
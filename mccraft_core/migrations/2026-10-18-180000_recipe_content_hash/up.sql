@@ -0,0 +1,4 @@
+-- A hash of everything that makes up a recipe, so that it can be found again
+-- after a re-import. Recipes imported before this was added don't have one.
+ALTER TABLE mccraft.recipes ADD COLUMN content_hash TEXT;
+CREATE UNIQUE INDEX recipe_content_hash ON mccraft.recipes (content_hash);
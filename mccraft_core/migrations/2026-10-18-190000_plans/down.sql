@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE mccraft.plan_recipes;
+DROP TABLE mccraft.plan_targets;
+DROP TABLE mccraft.plans;
@@ -0,0 +1,24 @@
+-- Crafting plans that users have saved, so that they can be shared by slug.
+CREATE TABLE mccraft.plans (
+  id SERIAL PRIMARY KEY,
+  slug TEXT NOT NULL UNIQUE,
+  name TEXT NOT NULL
+);
+
+CREATE TABLE mccraft.plan_targets (
+  id SERIAL PRIMARY KEY,
+  plan INTEGER NOT NULL REFERENCES mccraft.plans(id) ON DELETE CASCADE,
+  item INTEGER NOT NULL REFERENCES mccraft.items(id),
+  quantity INTEGER NOT NULL
+);
+CREATE INDEX plan_target_plan ON mccraft.plan_targets (plan);
+
+-- Recipes are referred to by their content hash, since their IDs change every
+-- time the recipes are re-imported.
+CREATE TABLE mccraft.plan_recipes (
+  id SERIAL PRIMARY KEY,
+  plan INTEGER NOT NULL REFERENCES mccraft.plans(id) ON DELETE CASCADE,
+  item INTEGER NOT NULL REFERENCES mccraft.items(id),
+  recipe_hash TEXT NOT NULL
+);
+CREATE INDEX plan_recipe_plan ON mccraft.plan_recipes (plan);
@@ -0,0 +1,4 @@
+-- Items that are gathered rather than crafted (ores, logs, mob drops...). The
+-- importer sets this from its raw item list, and the health report uses it to
+-- tell missing recipes apart from things that are supposed to have none.
+ALTER TABLE mccraft.items ADD COLUMN raw BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE mccraft.input_slots DROP COLUMN x, DROP COLUMN y;
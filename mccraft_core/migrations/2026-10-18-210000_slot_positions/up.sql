@@ -0,0 +1,3 @@
+-- Where each slot sits in the recipe's JEI layout, in pixels. Older imports
+-- don't have these.
+ALTER TABLE mccraft.input_slots ADD COLUMN x INTEGER, ADD COLUMN y INTEGER;
@@ -0,0 +1,3 @@
+-- Recipes an admin has taken out of the web interface. They stay in the table
+-- so that re-importing the same export doesn't bring them back.
+ALTER TABLE mccraft.recipes ADD COLUMN hidden BOOLEAN NOT NULL DEFAULT false;
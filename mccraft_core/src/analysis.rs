@@ -0,0 +1,425 @@
+//! Checks for problems in an imported recipe database.
+//!
+//! The whole recipe graph is loaded at once, so this is meant for occasional
+//! reports after an import rather than for serving requests.
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use schema::mccraft::{
+    crafting_components, ingredient_group_members, input_slots, items, machines, outputs, recipes,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReportItem {
+    pub item_id: i32,
+    pub item_name: String,
+    pub minecraft_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReportRecipe {
+    pub recipe_id: i32,
+    pub machine_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReportMachine {
+    pub machine_id: i32,
+    pub machine_name: String,
+}
+
+/// A set of items that can all be made from each other
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReportCycle {
+    pub items: Vec<ReportItem>,
+    /// The recipes that turn items in the cycle in to other items in the cycle
+    pub recipes: Vec<ReportRecipe>,
+}
+
+/// Everything that looks wrong with the recipe graph
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HealthReport {
+    /// Items that recipes need, but that nothing makes and that aren't marked
+    /// as raw materials
+    pub unobtainable: Vec<ReportItem>,
+    /// Items that recipes make, but that nothing uses
+    pub unused: Vec<ReportItem>,
+    /// Recipes that only take in things they also give back
+    pub self_recipes: Vec<ReportRecipe>,
+    /// Loops in the recipe graph
+    pub cycles: Vec<ReportCycle>,
+    /// Machines that don't have any recipes
+    pub idle_machines: Vec<ReportMachine>,
+}
+
+/// The parts of a recipe the analysis cares about
+struct RecipeEdges {
+    machine: i32,
+    inputs: BTreeSet<i32>,
+    outputs: BTreeSet<i32>,
+}
+
+impl HealthReport {
+    /// Load the recipe graph and look for problems in it
+    pub fn compute(conn: &PgConnection) -> QueryResult<HealthReport> {
+        let item_rows = items::table
+            .select((items::id, items::human_name, items::minecraft_id, items::raw))
+            .load::<(i32, String, String, bool)>(conn)?;
+        let machine_names: BTreeMap<i32, String> = machines::table
+            .select((machines::id, machines::human_name))
+            .load::<(i32, String)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut graph: BTreeMap<i32, RecipeEdges> = recipes::table
+            .filter(recipes::hidden.eq(false))
+            .select((recipes::id, recipes::machine))
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .map(|(id, machine)| {
+                (
+                    id,
+                    RecipeEdges {
+                        machine,
+                        inputs: BTreeSet::new(),
+                        outputs: BTreeSet::new(),
+                    },
+                )
+            }).collect();
+
+        let components = crafting_components::table
+            .inner_join(input_slots::table)
+            .select((input_slots::for_recipe, crafting_components::item))
+            .load::<(i32, i32)>(conn)?;
+        let grouped = ingredient_group_members::table
+            .inner_join(
+                input_slots::table.on(input_slots::ingredient_group
+                    .eq(ingredient_group_members::ingredient_group.nullable())),
+            ).select((input_slots::for_recipe, ingredient_group_members::item))
+            .load::<(i32, i32)>(conn)?;
+        for (recipe, item) in components.into_iter().chain(grouped.into_iter()) {
+            if let Some(edges) = graph.get_mut(&recipe) {
+                edges.inputs.insert(item);
+            }
+        }
+        for (recipe, item) in outputs::table
+            .select((outputs::recipe, outputs::item))
+            .load::<(i32, i32)>(conn)?
+        {
+            if let Some(edges) = graph.get_mut(&recipe) {
+                edges.outputs.insert(item);
+            }
+        }
+
+        let items: BTreeMap<i32, ReportItem> = item_rows
+            .iter()
+            .map(|(id, name, mcid, _)| {
+                (
+                    *id,
+                    ReportItem {
+                        item_id: *id,
+                        item_name: name.clone(),
+                        minecraft_id: mcid.clone(),
+                    },
+                )
+            }).collect();
+        let raw: HashSet<i32> = item_rows
+            .iter()
+            .filter(|x| x.3)
+            .map(|x| x.0)
+            .collect();
+
+        Ok(analyze(&graph, &items, &raw, &machine_names))
+    }
+
+    /// A summary for reading in a terminal
+    pub fn to_text(&self) -> String {
+        fn items(out: &mut String, title: &str, items: &[ReportItem]) {
+            writeln!(out, "{} ({})", title, items.len()).unwrap();
+            for item in items {
+                writeln!(out, "  {} [{}]", item.item_name, item.minecraft_id).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        let mut out = String::new();
+        items(&mut out, "Unobtainable items", &self.unobtainable);
+        items(&mut out, "Unused items", &self.unused);
+
+        writeln!(out, "Recipes that only return their inputs ({})", self.self_recipes.len()).unwrap();
+        for recipe in &self.self_recipes {
+            writeln!(out, "  #{} ({})", recipe.recipe_id, recipe.machine_name).unwrap();
+        }
+        writeln!(out).unwrap();
+
+        writeln!(out, "Recipe loops ({})", self.cycles.len()).unwrap();
+        for cycle in &self.cycles {
+            let names: Vec<&str> = cycle.items.iter().map(|x| x.item_name.as_str()).collect();
+            writeln!(out, "  {}", names.join(" <-> ")).unwrap();
+        }
+        writeln!(out).unwrap();
+
+        writeln!(out, "Machines without recipes ({})", self.idle_machines.len()).unwrap();
+        for machine in &self.idle_machines {
+            writeln!(out, "  {}", machine.machine_name).unwrap();
+        }
+
+        out
+    }
+}
+
+fn analyze(
+    graph: &BTreeMap<i32, RecipeEdges>,
+    items: &BTreeMap<i32, ReportItem>,
+    raw: &HashSet<i32>,
+    machine_names: &BTreeMap<i32, String>,
+) -> HealthReport {
+    let report_recipe = |id: i32| ReportRecipe {
+        recipe_id: id,
+        machine_name: machine_names
+            .get(&graph[&id].machine)
+            .cloned()
+            .unwrap_or_default(),
+    };
+    let report_items = |ids: &mut dyn Iterator<Item = &i32>| -> Vec<ReportItem> {
+        ids.filter_map(|x| items.get(x).cloned()).collect()
+    };
+
+    let used: BTreeSet<i32> = graph.values().flat_map(|x| x.inputs.iter().cloned()).collect();
+    let produced: BTreeSet<i32> = graph.values().flat_map(|x| x.outputs.iter().cloned()).collect();
+    let busy_machines: HashSet<i32> = graph.values().map(|x| x.machine).collect();
+
+    // Items turn in to other items through recipes
+    let mut edges: HashMap<i32, Vec<i32>> = HashMap::new();
+    for recipe in graph.values() {
+        for input in &recipe.inputs {
+            edges
+                .entry(*input)
+                .or_insert_with(Vec::new)
+                .extend(recipe.outputs.iter().filter(|x| *x != input));
+        }
+    }
+    let nodes: Vec<i32> = used.union(&produced).cloned().collect();
+
+    let mut components: Vec<Vec<i32>> = strongly_connected(&nodes, &edges)
+        .into_iter()
+        .filter(|x| x.len() > 1)
+        .collect();
+    let mut component_of: HashMap<i32, usize> = HashMap::new();
+    for (i, component) in components.iter_mut().enumerate() {
+        component.sort();
+        component_of.extend(component.iter().map(|x| (*x, i)));
+    }
+
+    // A recipe is part of a loop if it turns one item in the loop in to another
+    let mut loop_recipes: Vec<BTreeSet<i32>> = vec![BTreeSet::new(); components.len()];
+    for (id, recipe) in graph {
+        for input in &recipe.inputs {
+            if let Some(&i) = component_of.get(input) {
+                if recipe.outputs.iter().any(|x| component_of.get(x) == Some(&i)) {
+                    loop_recipes[i].insert(*id);
+                }
+            }
+        }
+    }
+
+    let cycles = components
+        .iter()
+        .zip(loop_recipes.iter())
+        .map(|(component, recipes)| ReportCycle {
+            items: report_items(&mut component.iter()),
+            recipes: recipes.iter().map(|x| report_recipe(*x)).collect(),
+        }).collect();
+
+    HealthReport {
+        unobtainable: report_items(
+            &mut used
+                .difference(&produced)
+                .filter(|x| !raw.contains(x)),
+        ),
+        unused: report_items(&mut produced.difference(&used)),
+        self_recipes: graph
+            .iter()
+            .filter(|(_, r)| !r.inputs.is_empty() && r.inputs.is_subset(&r.outputs))
+            .map(|(id, _)| report_recipe(*id))
+            .collect(),
+        cycles,
+        idle_machines: machine_names
+            .iter()
+            .filter(|(id, _)| !busy_machines.contains(id))
+            .map(|(id, name)| ReportMachine {
+                machine_id: *id,
+                machine_name: name.clone(),
+            }).collect(),
+    }
+}
+
+/// Tarjan's algorithm, without recursion since recipe chains can get deep
+pub(crate) fn strongly_connected(nodes: &[i32], edges: &HashMap<i32, Vec<i32>>) -> Vec<Vec<i32>> {
+    let no_edges = Vec::new();
+    let mut index: HashMap<i32, usize> = HashMap::new();
+    let mut lowlink: HashMap<i32, usize> = HashMap::new();
+    let mut on_stack: HashSet<i32> = HashSet::new();
+    let mut stack: Vec<i32> = Vec::new();
+    let mut components = Vec::new();
+
+    for &start in nodes {
+        if index.contains_key(&start) {
+            continue;
+        }
+
+        // (node, how many of its successors we've looked at)
+        let mut calls = vec![(start, 0)];
+        index.insert(start, index.len());
+        lowlink.insert(start, index[&start]);
+        stack.push(start);
+        on_stack.insert(start);
+
+        while let Some(&(node, next)) = calls.last() {
+            let successors = edges.get(&node).unwrap_or(&no_edges);
+            if next < successors.len() {
+                calls.last_mut().unwrap().1 += 1;
+                let successor = successors[next];
+                if !index.contains_key(&successor) {
+                    index.insert(successor, index.len());
+                    lowlink.insert(successor, index[&successor]);
+                    stack.push(successor);
+                    on_stack.insert(successor);
+                    calls.push((successor, 0));
+                } else if on_stack.contains(&successor) {
+                    let low = lowlink[&node].min(index[&successor]);
+                    lowlink.insert(node, low);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                let low = lowlink[&parent].min(lowlink[&node]);
+                lowlink.insert(parent, low);
+            }
+            if lowlink[&node] == index[&node] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack.remove(&member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(machine: i32, inputs: &[i32], outputs: &[i32]) -> RecipeEdges {
+        RecipeEdges {
+            machine,
+            inputs: inputs.iter().cloned().collect(),
+            outputs: outputs.iter().cloned().collect(),
+        }
+    }
+
+    fn item_ids(items: &[ReportItem]) -> Vec<i32> {
+        items.iter().map(|x| x.item_id).collect()
+    }
+
+    fn recipe_ids(recipes: &[ReportRecipe]) -> Vec<i32> {
+        recipes.iter().map(|x| x.recipe_id).collect()
+    }
+
+    #[test]
+    fn analyzes_a_recipe_graph() {
+        let mut graph = BTreeMap::new();
+        // Ore (1) to ingots (2), and ingots to blocks (3) and back
+        graph.insert(10, recipe(1, &[1], &[2]));
+        graph.insert(11, recipe(1, &[2], &[3]));
+        graph.insert(12, recipe(2, &[3], &[2]));
+        // Gears (6) from something nothing makes (4) and from water (5)
+        graph.insert(13, recipe(2, &[4, 5], &[6]));
+        // Washing a dirty item (7) in to itself
+        graph.insert(14, recipe(1, &[7], &[7]));
+
+        let items: BTreeMap<i32, ReportItem> = (1..8)
+            .map(|id| {
+                (
+                    id,
+                    ReportItem {
+                        item_id: id,
+                        item_name: format!("Item {}", id),
+                        minecraft_id: format!("test:item{}:0", id),
+                    },
+                )
+            }).collect();
+        let raw: HashSet<i32> = vec![1, 5].into_iter().collect();
+        let machine_names: BTreeMap<i32, String> = vec![
+            (1, "Crafting".to_owned()),
+            (2, "Press".to_owned()),
+            (3, "Idle".to_owned()),
+        ].into_iter()
+        .collect();
+
+        let report = analyze(&graph, &items, &raw, &machine_names);
+        assert_eq!(item_ids(&report.unobtainable), vec![4]);
+        assert_eq!(item_ids(&report.unused), vec![6]);
+        assert_eq!(recipe_ids(&report.self_recipes), vec![14]);
+        assert_eq!(report.self_recipes[0].machine_name, "Crafting");
+        assert_eq!(report.cycles.len(), 1);
+        assert_eq!(item_ids(&report.cycles[0].items), vec![2, 3]);
+        assert_eq!(recipe_ids(&report.cycles[0].recipes), vec![11, 12]);
+        assert_eq!(report.idle_machines.len(), 1);
+        assert_eq!(report.idle_machines[0].machine_id, 3);
+    }
+
+    fn components(nodes: &[i32], edges: &[(i32, i32)]) -> Vec<Vec<i32>> {
+        let mut graph: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &(from, to) in edges {
+            graph.entry(from).or_insert_with(Vec::new).push(to);
+        }
+        let mut components = strongly_connected(nodes, &graph);
+        for component in &mut components {
+            component.sort();
+        }
+        components.sort();
+        components
+    }
+
+    #[test]
+    fn finds_cycles() {
+        // 1 -> 2 -> 3 -> 1 is a cycle, and 3 -> 4 leads out of it
+        assert_eq!(
+            components(&[1, 2, 3, 4], &[(1, 2), (2, 3), (3, 1), (3, 4)]),
+            vec![vec![1, 2, 3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn keeps_separate_cycles_apart() {
+        assert_eq!(
+            components(&[1, 2, 3, 4, 5], &[(1, 2), (2, 1), (2, 3), (3, 4), (4, 3), (5, 5)]),
+            vec![vec![1, 2], vec![3, 4], vec![5]]
+        );
+    }
+
+    #[test]
+    fn handles_long_chains() {
+        // Deep enough to overflow the stack if this were recursive
+        let nodes: Vec<i32> = (0..100_000).collect();
+        let edges: Vec<(i32, i32)> = nodes.windows(2).map(|x| (x[0], x[1])).collect();
+        assert_eq!(components(&nodes, &edges).len(), nodes.len());
+
+        let mut cycle = edges.clone();
+        cycle.push((99_999, 0));
+        assert_eq!(components(&nodes, &cycle), vec![nodes.clone()]);
+    }
+}
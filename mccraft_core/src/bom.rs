@@ -0,0 +1,387 @@
+//! Bill of materials calculations.
+//!
+//! Everything in here works in terms of database item IDs and quantities, so
+//! that it can be shared between the web server and any offline tooling. The
+//! caller is responsible for loading the recipes and turning the results back
+//! in to something human-readable.
+
+use analysis::strongly_connected;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// A filled container that is equivalent to some amount of fluid plus an
+/// empty container (e.g. a Water Bucket is 1000 mB of water and a Bucket).
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
+pub struct ContainerEquivalence {
+    /// The item ID of the filled container
+    pub filled: i32,
+    /// The item ID of the fluid inside it
+    pub fluid: i32,
+    /// How much fluid the container holds
+    pub amount: i32,
+    /// The item ID of the container once it has been emptied, if there is
+    /// anything left
+    pub empty: Option<i32>,
+}
+
+/// Lookup table for container equivalences
+#[derive(Debug, Clone, Default)]
+pub struct FluidContainers {
+    by_filled: HashMap<i32, ContainerEquivalence>,
+}
+
+impl FluidContainers {
+    pub fn new(entries: impl IntoIterator<Item = ContainerEquivalence>) -> Self {
+        FluidContainers {
+            by_filled: entries.into_iter().map(|x| (x.filled, x)).collect(),
+        }
+    }
+
+    /// Rewrite a quantity of some item so that filled containers are replaced
+    /// with the fluid they hold plus an empty container. Anything that isn't a
+    /// filled container is returned unchanged.
+    pub fn normalize(&self, item: i32, quantity: i64) -> Vec<(i32, i64)> {
+        match self.by_filled.get(&item) {
+            Some(container) => {
+                let mut tr = vec![(container.fluid, quantity * container.amount as i64)];
+                if let Some(empty) = container.empty {
+                    tr.push((empty, quantity));
+                }
+                tr
+            }
+            None => vec![(item, quantity)],
+        }
+    }
+}
+
+/// One input slot of a process
+#[derive(Debug, Clone)]
+pub struct ProcessInput {
+    /// All the (item, quantity) pairs that are accepted in this slot
+    pub alternatives: Vec<(i32, i64)>,
+    /// False for tools and catalysts, which only need to be made once per
+    /// plan rather than once per craft
+    pub consumed: bool,
+}
+
+/// A recipe, reduced to what the BOM calculation cares about
+#[derive(Debug, Clone)]
+pub struct Process {
+    pub recipe_id: i32,
+    pub inputs: Vec<ProcessInput>,
+    pub outputs: Vec<(i32, i64)>,
+}
+
+/// The result of a BOM calculation
+#[derive(Debug, Clone, Default)]
+pub struct Bom {
+    /// Things that have to be gathered by hand, by item ID
+    pub raw: BTreeMap<i32, i64>,
+    /// How many times each recipe has to be performed, by recipe ID
+    pub crafts: BTreeMap<i32, i64>,
+    /// Things that are produced but not needed, by item ID
+    pub leftovers: BTreeMap<i32, i64>,
+    /// Tools and catalysts that are needed but not used up, by item ID. These
+    /// are also accounted for (once) in `raw` and `crafts`.
+    pub tools: BTreeMap<i32, i64>,
+}
+
+/// Compute what is needed to make `targets`, using the recipe chosen for each
+/// item in `processes` (keyed by the item ID the recipe was chosen for).
+/// Anything without a chosen recipe is considered raw, and so is anything whose
+/// chosen recipes lead back to itself.
+pub fn compute(
+    targets: &[(i32, i64)],
+    processes: &HashMap<i32, Process>,
+    containers: &FluidContainers,
+) -> Bom {
+    // Map from the item we need to the process that makes it. A recipe chosen
+    // for a filled container is also good for the fluid inside of it.
+    let mut producers: HashMap<i32, &Process> = HashMap::new();
+    for (item, process) in processes.iter() {
+        if let Some(container) = containers.by_filled.get(item) {
+            producers.entry(container.fluid).or_insert(process);
+        }
+    }
+    for (item, process) in processes.iter() {
+        producers.insert(*item, process);
+    }
+
+    let mut calculator = Calculator {
+        producers,
+        containers,
+        cyclic: HashSet::new(),
+        bom: Bom::default(),
+        surplus: HashMap::new(),
+    };
+    calculator.cyclic = calculator.find_cycles();
+
+    let mut needed: Vec<(i32, i64)> = targets
+        .iter()
+        .flat_map(|&(item, quantity)| containers.normalize(item, quantity))
+        .collect();
+
+    // Tools only have to be made once, but making a tool may need other tools,
+    // so keep going until we stop finding new ones.
+    let mut tools_made: HashMap<i32, i64> = HashMap::new();
+    while !needed.is_empty() {
+        calculator.satisfy(needed);
+
+        needed = calculator
+            .bom
+            .tools
+            .iter()
+            .filter_map(|(&item, &quantity)| {
+                let made = tools_made.entry(item).or_insert(0);
+                if *made >= quantity {
+                    return None;
+                }
+                let missing = quantity - *made;
+                *made = quantity;
+                Some((item, missing))
+            }).flat_map(|(item, quantity)| containers.normalize(item, quantity))
+            .collect();
+    }
+
+    let mut bom = calculator.bom;
+    bom.leftovers = calculator.surplus.into_iter().filter(|x| x.1 > 0).collect();
+
+    bom
+}
+
+/// State for an in-progress BOM calculation
+struct Calculator<'a> {
+    producers: HashMap<i32, &'a Process>,
+    containers: &'a FluidContainers,
+    /// Items whose chosen recipes need the item itself, directly or further
+    /// down. These can't be planned, so they're treated as raw.
+    cyclic: HashSet<i32>,
+    bom: Bom,
+    /// Things that have been made but not used yet
+    surplus: HashMap<i32, i64>,
+}
+
+impl<'a> Calculator<'a> {
+    /// The process to make `item` with, along with what one craft of it
+    /// produces. None if the item has to be gathered instead.
+    fn producer(&self, item: i32) -> Option<(&'a Process, Vec<(i32, i64)>)> {
+        let process = *self.producers.get(&item)?;
+        let outputs: Vec<(i32, i64)> = process
+            .outputs
+            .iter()
+            .flat_map(|&(item, quantity)| self.containers.normalize(item, quantity))
+            .collect();
+        // The chosen recipe might not actually make this
+        if outputs.iter().filter(|x| x.0 == item).map(|x| x.1).sum::<i64>() <= 0 {
+            return None;
+        }
+        Some((process, outputs))
+    }
+
+    /// The alternative to use for an input slot: the first one we have a
+    /// recipe for, or else the first one
+    fn choose(&self, input: &ProcessInput) -> Option<(i32, i64)> {
+        input
+            .alternatives
+            .iter()
+            .find(|x| self.producers.contains_key(&x.0))
+            .or_else(|| input.alternatives.first())
+            .cloned()
+    }
+
+    /// Find every item that the chosen recipes would make out of itself
+    fn find_cycles(&self) -> HashSet<i32> {
+        let mut edges: HashMap<i32, Vec<i32>> = HashMap::new();
+        for &item in self.producers.keys() {
+            let process = match self.producer(item) {
+                Some((process, _)) => process,
+                None => continue,
+            };
+            let inputs = edges.entry(item).or_insert_with(Vec::new);
+            for input in process.inputs.iter().filter(|x| x.consumed) {
+                if let Some((input, quantity)) = self.choose(input) {
+                    inputs.extend(self.containers.normalize(input, quantity).iter().map(|x| x.0));
+                }
+            }
+        }
+
+        let nodes: Vec<i32> = edges.keys().cloned().collect();
+        strongly_connected(&nodes, &edges)
+            .into_iter()
+            .filter(|x| {
+                x.len() > 1 || edges.get(&x[0]).map_or(false, |inputs| inputs.contains(&x[0]))
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Work out how to make everything in `needed`, a list of (item, quantity)
+    /// pairs. Tools that are needed along the way are recorded, but not made.
+    fn satisfy(&mut self, mut needed: Vec<(i32, i64)>) {
+        while let Some((item, mut quantity)) = needed.pop() {
+            // Use up anything left over from earlier crafts first
+            if let Some(available) = self.surplus.get_mut(&item) {
+                let used = quantity.min(*available);
+                *available -= used;
+                quantity -= used;
+            }
+            if quantity <= 0 {
+                continue;
+            }
+
+            let (process, outputs) = match self.producer(item) {
+                Some(producer) if !self.cyclic.contains(&item) => producer,
+                _ => {
+                    *self.bom.raw.entry(item).or_insert(0) += quantity;
+                    continue;
+                }
+            };
+            let per_craft: i64 = outputs
+                .iter()
+                .filter(|x| x.0 == item)
+                .map(|x| x.1)
+                .sum();
+
+            let crafts = (quantity + per_craft - 1) / per_craft;
+            *self.bom.crafts.entry(process.recipe_id).or_insert(0) += crafts;
+
+            for (output, amount) in outputs {
+                let produced = amount * crafts;
+                let extra = if output == item {
+                    produced - quantity
+                } else {
+                    produced
+                };
+                if extra > 0 {
+                    *self.surplus.entry(output).or_insert(0) += extra;
+                }
+            }
+
+            for input in &process.inputs {
+                let (input_item, input_quantity) = match self.choose(input) {
+                    Some(chosen) => chosen,
+                    None => continue,
+                };
+
+                if !input.consumed {
+                    // We need enough for a single craft, for the whole plan
+                    let tool = self.bom.tools.entry(input_item).or_insert(0);
+                    *tool = (*tool).max(input_quantity);
+                    continue;
+                }
+
+                for (input_item, input_quantity) in self
+                    .containers
+                    .normalize(input_item, input_quantity * crafts)
+                {
+                    needed.push((input_item, input_quantity));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(recipe_id: i32, inputs: &[(i32, i64)], outputs: &[(i32, i64)]) -> Process {
+        Process {
+            recipe_id,
+            inputs: inputs
+                .iter()
+                .map(|&x| ProcessInput {
+                    alternatives: vec![x],
+                    consumed: true,
+                }).collect(),
+            outputs: outputs.to_vec(),
+        }
+    }
+
+    fn map(entries: &[(i32, i64)]) -> BTreeMap<i32, i64> {
+        entries.iter().cloned().collect()
+    }
+
+    #[test]
+    fn rounds_up_crafts_and_keeps_leftovers() {
+        let mut processes = HashMap::new();
+        processes.insert(10, process(1, &[(20, 1)], &[(10, 4)]));
+
+        let bom = compute(&[(10, 6)], &processes, &FluidContainers::default());
+        assert_eq!(bom.crafts, map(&[(1, 2)]));
+        assert_eq!(bom.raw, map(&[(20, 2)]));
+        assert_eq!(bom.leftovers, map(&[(10, 2)]));
+        assert!(bom.tools.is_empty());
+    }
+
+    #[test]
+    fn uses_leftovers_before_crafting_again() {
+        // Both 11 and 12 need a 10, and one craft makes two of them
+        let mut processes = HashMap::new();
+        processes.insert(10, process(1, &[(20, 1)], &[(10, 2)]));
+        processes.insert(11, process(2, &[(10, 1)], &[(11, 1)]));
+        processes.insert(12, process(3, &[(10, 1)], &[(12, 1)]));
+
+        let bom = compute(&[(11, 1), (12, 1)], &processes, &FluidContainers::default());
+        assert_eq!(bom.crafts, map(&[(1, 1), (2, 1), (3, 1)]));
+        assert_eq!(bom.raw, map(&[(20, 1)]));
+        assert!(bom.leftovers.is_empty());
+    }
+
+    #[test]
+    fn items_in_cycles_are_raw() {
+        // Ingots from blocks and blocks from ingots
+        let mut processes = HashMap::new();
+        processes.insert(1, process(1, &[(2, 1)], &[(1, 9)]));
+        processes.insert(2, process(2, &[(1, 9)], &[(2, 1)]));
+        processes.insert(3, process(3, &[(1, 2)], &[(3, 1)]));
+
+        let bom = compute(&[(1, 1)], &processes, &FluidContainers::default());
+        assert_eq!(bom.raw, map(&[(1, 1)]));
+        assert!(bom.crafts.is_empty());
+
+        // Things made from the cycle are still crafted
+        let bom = compute(&[(3, 1)], &processes, &FluidContainers::default());
+        assert_eq!(bom.raw, map(&[(1, 2)]));
+        assert_eq!(bom.crafts, map(&[(3, 1)]));
+    }
+
+    #[test]
+    fn recipes_that_need_their_own_output_are_raw() {
+        let mut processes = HashMap::new();
+        processes.insert(1, process(1, &[(1, 1), (2, 1)], &[(1, 2)]));
+
+        let bom = compute(&[(1, 3)], &processes, &FluidContainers::default());
+        assert_eq!(bom.raw, map(&[(1, 3)]));
+        assert!(bom.crafts.is_empty());
+    }
+
+    #[test]
+    fn splits_filled_containers() {
+        let containers = FluidContainers::new(vec![ContainerEquivalence {
+            filled: 100,
+            fluid: 101,
+            amount: 1000,
+            empty: Some(102),
+        }]);
+
+        let bom = compute(&[(100, 2)], &HashMap::new(), &containers);
+        assert_eq!(bom.raw, map(&[(101, 2000), (102, 2)]));
+    }
+
+    #[test]
+    fn makes_tools_once() {
+        let mut processes = HashMap::new();
+        let mut craft = process(1, &[(20, 1)], &[(10, 1)]);
+        craft.inputs.push(ProcessInput {
+            alternatives: vec![(30, 1)],
+            consumed: false,
+        });
+        processes.insert(10, craft);
+        processes.insert(30, process(2, &[(40, 3)], &[(30, 1)]));
+
+        let bom = compute(&[(10, 5)], &processes, &FluidContainers::default());
+        assert_eq!(bom.crafts, map(&[(1, 5), (2, 1)]));
+        assert_eq!(bom.tools, map(&[(30, 1)]));
+        assert_eq!(bom.raw, map(&[(20, 5), (40, 3)]));
+    }
+}
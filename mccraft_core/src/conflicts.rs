@@ -0,0 +1,434 @@
+//! Finds recipe conflicts: recipes on the same machine that can be fed the
+//! same inputs but make different things.
+//!
+//! JEI gives the slots of shapeless recipes positions too, so positions alone
+//! don't say whether a recipe is shaped. Recipes in the categories the caller
+//! names as shaped are compared position by position, after shifting both
+//! layouts to the top left corner so a recipe matches wherever it was drawn in
+//! the grid. Everything else, and shaped recipes that are missing positions,
+//! are compared as an unordered set of slots.
+
+use analysis::ReportItem;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use schema::mccraft::{
+    crafting_components, ingredient_group_members, input_slots, items, machines, outputs, recipes,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// One side of a conflict
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConflictRecipe {
+    pub recipe_id: i32,
+    pub outputs: Vec<ReportItem>,
+}
+
+/// Two recipes that accept the same inputs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Conflict {
+    pub first: ConflictRecipe,
+    pub second: ConflictRecipe,
+    /// Whether the recipes were compared slot by slot, rather than as
+    /// unordered sets of slots
+    pub positional: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MachineConflicts {
+    pub machine_id: i32,
+    pub machine_name: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Every conflict in the database, grouped by machine
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ConflictReport {
+    pub machines: Vec<MachineConflicts>,
+}
+
+/// A slot of a recipe, as far as conflict detection cares
+struct Slot {
+    position: Option<(i32, i32)>,
+    alternatives: BTreeSet<i32>,
+}
+
+struct RecipeInputs {
+    machine: i32,
+    slots: Vec<Slot>,
+    /// (item, quantity), sorted
+    outputs: Vec<(i32, i32)>,
+}
+
+impl RecipeInputs {
+    /// The slot positions shifted so the top left one is at the origin, or
+    /// None if any slot doesn't have a position
+    fn layout(&self) -> Option<Vec<(i32, i32)>> {
+        let positions: Vec<(i32, i32)> = self
+            .slots
+            .iter()
+            .map(|x| x.position)
+            .collect::<Option<_>>()?;
+        let min_x = positions.iter().map(|x| x.0).min()?;
+        let min_y = positions.iter().map(|x| x.1).min()?;
+        Some(
+            positions
+                .into_iter()
+                .map(|(x, y)| (x - min_x, y - min_y))
+                .collect(),
+        )
+    }
+
+    /// Slot alternatives ordered by their position in `layout`
+    fn slots_by_layout(&self, layout: &[(i32, i32)]) -> BTreeMap<(i32, i32), &BTreeSet<i32>> {
+        layout
+            .iter()
+            .cloned()
+            .zip(self.slots.iter().map(|x| &x.alternatives))
+            .collect()
+    }
+}
+
+impl ConflictReport {
+    /// Load every recipe and look for conflicts between them.
+    /// `shaped_categories` are the JEI category UIDs whose recipes care where
+    /// their ingredients go.
+    pub fn compute(
+        conn: &PgConnection,
+        shaped_categories: &[String],
+    ) -> QueryResult<ConflictReport> {
+        let mut recipes: BTreeMap<i32, RecipeInputs> = recipes::table
+            .filter(recipes::hidden.eq(false))
+            .select((recipes::id, recipes::machine))
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .map(|(id, machine)| {
+                (
+                    id,
+                    RecipeInputs {
+                        machine,
+                        slots: Vec::new(),
+                        outputs: Vec::new(),
+                    },
+                )
+            }).collect();
+
+        // Which recipe, and which slot of that recipe, each slot ID is
+        let mut slot_index: HashMap<i32, (i32, usize)> = HashMap::new();
+        for (id, recipe_id, x, y) in input_slots::table
+            .select((input_slots::id, input_slots::for_recipe, input_slots::x, input_slots::y))
+            .order_by(input_slots::id)
+            .load::<(i32, i32, Option<i32>, Option<i32>)>(conn)?
+        {
+            if let Some(recipe) = recipes.get_mut(&recipe_id) {
+                slot_index.insert(id, (recipe_id, recipe.slots.len()));
+                recipe.slots.push(Slot {
+                    position: x.and_then(|x| y.map(|y| (x, y))),
+                    alternatives: BTreeSet::new(),
+                });
+            }
+        }
+
+        let components = crafting_components::table
+            .select((crafting_components::crafting_slot, crafting_components::item))
+            .load::<(i32, i32)>(conn)?;
+        let grouped = ingredient_group_members::table
+            .inner_join(
+                input_slots::table.on(input_slots::ingredient_group
+                    .eq(ingredient_group_members::ingredient_group.nullable())),
+            ).select((input_slots::id, ingredient_group_members::item))
+            .load::<(i32, i32)>(conn)?;
+        for (slot, item) in components.into_iter().chain(grouped.into_iter()) {
+            if let Some(&(recipe_id, i)) = slot_index.get(&slot) {
+                recipes.get_mut(&recipe_id).unwrap().slots[i]
+                    .alternatives
+                    .insert(item);
+            }
+        }
+
+        for (recipe_id, item, quantity) in outputs::table
+            .select((outputs::recipe, outputs::item, outputs::quantity))
+            .load::<(i32, i32, i32)>(conn)?
+        {
+            if let Some(recipe) = recipes.get_mut(&recipe_id) {
+                recipe.outputs.push((item, quantity));
+            }
+        }
+        for recipe in recipes.values_mut() {
+            recipe.outputs.sort();
+        }
+
+        let item_names: HashMap<i32, ReportItem> = items::table
+            .select((items::id, items::human_name, items::minecraft_id))
+            .load::<(i32, String, String)>(conn)?
+            .into_iter()
+            .map(|(id, name, mcid)| {
+                (
+                    id,
+                    ReportItem {
+                        item_id: id,
+                        item_name: name,
+                        minecraft_id: mcid,
+                    },
+                )
+            }).collect();
+        let machine_rows = machines::table
+            .select((machines::id, machines::human_name, machines::minecraft_id))
+            .load::<(i32, String, String)>(conn)?;
+        let shaped: HashSet<i32> = machine_rows
+            .iter()
+            .filter(|x| shaped_categories.contains(&x.2))
+            .map(|x| x.0)
+            .collect();
+        let machine_names: HashMap<i32, String> = machine_rows
+            .into_iter()
+            .map(|(id, name, _)| (id, name))
+            .collect();
+
+        let describe = |recipe_id: i32| ConflictRecipe {
+            recipe_id,
+            outputs: recipes[&recipe_id]
+                .outputs
+                .iter()
+                .filter_map(|(item, _)| item_names.get(item).cloned())
+                .collect(),
+        };
+
+        let mut by_machine: BTreeMap<i32, Vec<Conflict>> = BTreeMap::new();
+        for (first, second, positional) in find_conflicts(&recipes, &shaped) {
+            by_machine
+                .entry(recipes[&first].machine)
+                .or_insert_with(Vec::new)
+                .push(Conflict {
+                    first: describe(first),
+                    second: describe(second),
+                    positional,
+                });
+        }
+
+        Ok(ConflictReport {
+            machines: by_machine
+                .into_iter()
+                .map(|(machine_id, conflicts)| MachineConflicts {
+                    machine_id,
+                    machine_name: machine_names.get(&machine_id).cloned().unwrap_or_default(),
+                    conflicts,
+                }).collect(),
+        })
+    }
+
+    /// A summary for reading in a terminal
+    pub fn to_text(&self) -> String {
+        fn outputs(recipe: &ConflictRecipe) -> String {
+            let names: Vec<&str> = recipe.outputs.iter().map(|x| x.item_name.as_str()).collect();
+            format!("#{} ({})", recipe.recipe_id, names.join(", "))
+        }
+
+        let mut out = String::new();
+        for machine in &self.machines {
+            writeln!(out, "{} ({})", machine.machine_name, machine.conflicts.len()).unwrap();
+            for conflict in &machine.conflicts {
+                writeln!(
+                    out,
+                    "  {} <-> {}{}",
+                    outputs(&conflict.first),
+                    outputs(&conflict.second),
+                    if conflict.positional { "" } else { " [shapeless]" }
+                ).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+        if self.machines.is_empty() {
+            writeln!(out, "No conflicts found").unwrap();
+        }
+        out
+    }
+}
+
+/// Find every pair of conflicting recipes, along with whether they were
+/// compared by position. Only recipes on the machines in `shaped` are.
+fn find_conflicts(
+    recipes: &BTreeMap<i32, RecipeInputs>,
+    shaped: &HashSet<i32>,
+) -> Vec<(i32, i32, bool)> {
+    // Only recipes on the same machine with the same layout (or the same
+    // number of slots, if they aren't compared by position) can conflict
+    let mut buckets: BTreeMap<(i32, Option<Vec<(i32, i32)>>, usize), Vec<i32>> = BTreeMap::new();
+    for (id, recipe) in recipes {
+        if recipe.slots.is_empty() {
+            continue;
+        }
+        let mut layout = if shaped.contains(&recipe.machine) {
+            recipe.layout()
+        } else {
+            None
+        };
+        if let Some(ref mut layout) = layout {
+            layout.sort();
+        }
+        buckets
+            .entry((recipe.machine, layout, recipe.slots.len()))
+            .or_insert_with(Vec::new)
+            .push(*id);
+    }
+
+    let mut conflicts = Vec::new();
+    for ((_, layout, _), ids) in buckets {
+        // Any conflicting recipe has to accept something from the first slot
+        // somewhere, so only those are compared
+        let mut accepting: HashMap<i32, BTreeSet<i32>> = HashMap::new();
+        for id in &ids {
+            for slot in &recipes[id].slots {
+                for item in &slot.alternatives {
+                    accepting.entry(*item).or_insert_with(BTreeSet::new).insert(*id);
+                }
+            }
+        }
+
+        for id in &ids {
+            let recipe = &recipes[id];
+            let candidates: BTreeSet<i32> = recipe.slots[0]
+                .alternatives
+                .iter()
+                .filter_map(|x| accepting.get(x))
+                .flat_map(|x| x.range(id + 1..).cloned())
+                .collect();
+            for other_id in candidates {
+                let other = &recipes[&other_id];
+                if recipe.outputs == other.outputs {
+                    // Duplicates rather than conflicts
+                    continue;
+                }
+                let overlaps = if layout.is_some() {
+                    positional_overlap(recipe, other)
+                } else {
+                    unordered_overlap(recipe, other)
+                };
+                if overlaps {
+                    conflicts.push((*id, other_id, layout.is_some()));
+                }
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Every slot shares an alternative with the slot in the same position
+fn positional_overlap(a: &RecipeInputs, b: &RecipeInputs) -> bool {
+    let (layout_a, layout_b) = match (a.layout(), b.layout()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return false,
+    };
+    let slots_b = b.slots_by_layout(&layout_b);
+    a.slots_by_layout(&layout_a)
+        .into_iter()
+        .all(|(position, alternatives)| {
+            slots_b
+                .get(&position)
+                .map(|x| !x.is_disjoint(alternatives))
+                .unwrap_or(false)
+        })
+}
+
+/// The slots can be paired up so every pair shares an alternative
+fn unordered_overlap(a: &RecipeInputs, b: &RecipeInputs) -> bool {
+    if a.slots.len() != b.slots.len() {
+        return false;
+    }
+
+    // Kuhn's augmenting path matching. Recipes only have a handful of slots.
+    fn augment(
+        a: &RecipeInputs,
+        b: &RecipeInputs,
+        slot: usize,
+        visited: &mut Vec<bool>,
+        matched: &mut Vec<Option<usize>>,
+    ) -> bool {
+        for other in 0..b.slots.len() {
+            let alternatives = &b.slots[other].alternatives;
+            if visited[other] || a.slots[slot].alternatives.is_disjoint(alternatives) {
+                continue;
+            }
+            visited[other] = true;
+            let free = match matched[other] {
+                None => true,
+                Some(previous) => augment(a, b, previous, visited, matched),
+            };
+            if free {
+                matched[other] = Some(slot);
+                return true;
+            }
+        }
+        false
+    }
+
+    let mut matched = vec![None; b.slots.len()];
+    (0..a.slots.len()).all(|slot| {
+        let mut visited = vec![false; b.slots.len()];
+        augment(a, b, slot, &mut visited, &mut matched)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A recipe on machine 1 that makes `output` from slots of
+    /// ((x, y), alternatives)
+    fn recipe(slots: &[((i32, i32), &[i32])], output: i32) -> RecipeInputs {
+        RecipeInputs {
+            machine: 1,
+            slots: slots
+                .iter()
+                .map(|(position, alternatives)| Slot {
+                    position: Some(*position),
+                    alternatives: alternatives.iter().cloned().collect(),
+                }).collect(),
+            outputs: vec![(output, 1)],
+        }
+    }
+
+    #[test]
+    fn positional_overlap_ignores_where_the_recipe_was_drawn() {
+        let a = recipe(&[((0, 0), &[1]), ((0, 1), &[2])], 10);
+        let b = recipe(&[((1, 1), &[1, 3]), ((1, 2), &[2])], 11);
+        assert!(positional_overlap(&a, &b));
+    }
+
+    #[test]
+    fn positional_overlap_needs_every_position_to_match() {
+        let a = recipe(&[((0, 0), &[1]), ((0, 1), &[2])], 10);
+        let swapped = recipe(&[((0, 0), &[2]), ((0, 1), &[1])], 11);
+        assert!(!positional_overlap(&a, &swapped));
+        assert!(unordered_overlap(&a, &swapped));
+    }
+
+    #[test]
+    fn unordered_overlap_finds_a_pairing() {
+        // Slot 0 of `a` could go with either slot of `b`, but slot 1 only
+        // fits b's slot 0, so the first choice has to be undone
+        let a = recipe(&[((0, 0), &[1, 2]), ((0, 1), &[1])], 10);
+        let b = recipe(&[((0, 0), &[1]), ((0, 1), &[2])], 11);
+        assert!(unordered_overlap(&a, &b));
+
+        let c = recipe(&[((0, 0), &[1]), ((0, 1), &[1])], 12);
+        assert!(!unordered_overlap(&c, &b));
+        let shorter = recipe(&[((0, 0), &[1])], 13);
+        assert!(!unordered_overlap(&shorter, &b));
+    }
+
+    #[test]
+    fn only_shaped_machines_compare_by_position() {
+        let mut recipes = BTreeMap::new();
+        recipes.insert(1, recipe(&[((0, 0), &[1]), ((18, 0), &[2])], 10));
+        recipes.insert(2, recipe(&[((0, 0), &[2]), ((18, 0), &[1])], 11));
+        // Same inputs and outputs as the first, so a copy and not a conflict
+        recipes.insert(3, recipe(&[((0, 0), &[2]), ((18, 0), &[1])], 10));
+
+        assert_eq!(find_conflicts(&recipes, &HashSet::new()), vec![(1, 2, false), (2, 3, false)]);
+
+        let shaped: HashSet<i32> = vec![1].into_iter().collect();
+        assert_eq!(find_conflicts(&recipes, &shaped), vec![(2, 3, true)]);
+    }
+}
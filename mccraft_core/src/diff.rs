@@ -0,0 +1,530 @@
+//! Changelogs between two versions of a pack.
+//!
+//! Both versions are boiled down to a `Snapshot` that only refers to things
+//! by their Minecraft ID, so a snapshot of an export folder can be compared
+//! with one loaded from a database, whatever row IDs the database assigned.
+
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use schema::mccraft::{
+    crafting_components, ingredient_group_members, input_slots, items, machines, outputs, recipes,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Some amount of an item
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stack {
+    pub minecraft_id: String,
+    pub quantity: i32,
+}
+
+/// A recipe, with its slots and alternatives in a fixed order
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SnapshotRecipe {
+    /// The category UID of the machine
+    pub machine: String,
+    /// The alternatives for each slot
+    pub inputs: Vec<Vec<Stack>>,
+    pub outputs: Vec<Stack>,
+}
+
+impl SnapshotRecipe {
+    pub fn new(machine: String, inputs: Vec<Vec<Stack>>, outputs: Vec<Stack>) -> Self {
+        let mut inputs: Vec<Vec<Stack>> = inputs
+            .into_iter()
+            .map(|mut x| {
+                x.sort();
+                x
+            }).collect();
+        inputs.sort();
+        let mut outputs = outputs;
+        outputs.sort();
+        SnapshotRecipe {
+            machine,
+            inputs,
+            outputs,
+        }
+    }
+
+    /// What a recipe has to keep to count as a changed version of another one
+    fn identity(&self) -> (String, Vec<String>) {
+        (
+            self.machine.clone(),
+            self.outputs.iter().map(|x| x.minecraft_id.clone()).collect(),
+        )
+    }
+}
+
+/// Everything about a pack version that a changelog cares about
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Item names, by Minecraft ID
+    pub items: BTreeMap<String, String>,
+    /// Machine names, by category UID
+    pub machines: BTreeMap<String, String>,
+    /// Every recipe. A recipe that's in the pack more than once is listed
+    /// once for each copy.
+    pub recipes: Vec<SnapshotRecipe>,
+}
+
+impl Snapshot {
+    /// How many copies of each recipe there are
+    fn recipe_counts(&self) -> BTreeMap<&SnapshotRecipe, usize> {
+        let mut counts = BTreeMap::new();
+        for recipe in &self.recipes {
+            *counts.entry(recipe).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+impl Snapshot {
+    /// Load a snapshot of everything in the database
+    pub fn load(conn: &PgConnection) -> QueryResult<Snapshot> {
+        let item_rows = items::table
+            .select((items::id, items::minecraft_id, items::human_name))
+            .load::<(i32, String, String)>(conn)?;
+        let machine_rows = machines::table
+            .select((machines::id, machines::minecraft_id, machines::human_name))
+            .load::<(i32, String, String)>(conn)?;
+        let item_ids: HashMap<i32, String> = item_rows
+            .iter()
+            .map(|(id, mcid, _)| (*id, mcid.clone()))
+            .collect();
+        let machine_ids: HashMap<i32, String> = machine_rows
+            .iter()
+            .map(|(id, mcid, _)| (*id, mcid.clone()))
+            .collect();
+        let stack = |item: i32, quantity: i32| Stack {
+            minecraft_id: item_ids.get(&item).cloned().unwrap_or_default(),
+            quantity,
+        };
+
+        let recipe_rows = recipes::table
+            .filter(recipes::hidden.eq(false))
+            .select((recipes::id, recipes::machine))
+            .load::<(i32, i32)>(conn)?;
+        let slot_recipes: HashMap<i32, i32> = input_slots::table
+            .select((input_slots::id, input_slots::for_recipe))
+            .load::<(i32, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut slots: BTreeMap<i32, Vec<Stack>> =
+            slot_recipes.keys().map(|x| (*x, Vec::new())).collect();
+        let components = crafting_components::table
+            .select((
+                crafting_components::crafting_slot,
+                crafting_components::item,
+                crafting_components::quantity,
+            )).load::<(i32, i32, i32)>(conn)?;
+        let grouped = ingredient_group_members::table
+            .inner_join(
+                input_slots::table.on(input_slots::ingredient_group
+                    .eq(ingredient_group_members::ingredient_group.nullable())),
+            ).select((
+                input_slots::id,
+                ingredient_group_members::item,
+                ingredient_group_members::quantity,
+            )).load::<(i32, i32, i32)>(conn)?;
+        for (slot, item, quantity) in components.into_iter().chain(grouped.into_iter()) {
+            if let Some(stacks) = slots.get_mut(&slot) {
+                stacks.push(stack(item, quantity));
+            }
+        }
+
+        let mut inputs: HashMap<i32, Vec<Vec<Stack>>> = HashMap::new();
+        for (slot, stacks) in slots {
+            if stacks.is_empty() {
+                continue;
+            }
+            inputs
+                .entry(slot_recipes[&slot])
+                .or_insert_with(Vec::new)
+                .push(stacks);
+        }
+        let mut produced: HashMap<i32, Vec<Stack>> = HashMap::new();
+        for (recipe, item, quantity) in outputs::table
+            .select((outputs::recipe, outputs::item, outputs::quantity))
+            .load::<(i32, i32, i32)>(conn)?
+        {
+            produced
+                .entry(recipe)
+                .or_insert_with(Vec::new)
+                .push(stack(item, quantity));
+        }
+
+        let recipes = recipe_rows
+            .into_iter()
+            .map(|(id, machine)| {
+                SnapshotRecipe::new(
+                    machine_ids.get(&machine).cloned().unwrap_or_default(),
+                    inputs.remove(&id).unwrap_or_default(),
+                    produced.remove(&id).unwrap_or_default(),
+                )
+            }).collect();
+
+        Ok(Snapshot {
+            items: item_rows
+                .into_iter()
+                .map(|(_, mcid, name)| (mcid, name))
+                .collect(),
+            machines: machine_rows
+                .into_iter()
+                .map(|(_, mcid, name)| (mcid, name))
+                .collect(),
+            recipes,
+        })
+    }
+}
+
+/// An item that only one version has
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiffItem {
+    pub minecraft_id: String,
+    pub name: String,
+}
+
+/// An item whose name changed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RenamedItem {
+    pub minecraft_id: String,
+    pub old_name: String,
+    pub new_name: String,
+}
+
+/// A recipe that makes the same things on the same machine, but takes
+/// different inputs or makes different amounts
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChangedRecipe {
+    pub old: SnapshotRecipe,
+    pub new: SnapshotRecipe,
+}
+
+/// Changes to the recipes for an item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemDiff {
+    pub minecraft_id: String,
+    pub name: String,
+    pub added: Vec<SnapshotRecipe>,
+    pub removed: Vec<SnapshotRecipe>,
+    pub changed: Vec<ChangedRecipe>,
+}
+
+/// Changes to the recipes of a machine, by the item they make
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MachineDiff {
+    pub machine: String,
+    pub name: String,
+    pub items: Vec<ItemDiff>,
+}
+
+/// Everything that changed between two versions of a pack
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PackDiff {
+    pub added_items: Vec<DiffItem>,
+    pub removed_items: Vec<DiffItem>,
+    pub renamed_items: Vec<RenamedItem>,
+    pub machines: Vec<MachineDiff>,
+}
+
+impl PackDiff {
+    pub fn compute(old: &Snapshot, new: &Snapshot) -> PackDiff {
+        let item = |snapshot: &Snapshot, mcid: &str| DiffItem {
+            minecraft_id: mcid.to_string(),
+            name: snapshot.items.get(mcid).cloned().unwrap_or_default(),
+        };
+        let added_items = new
+            .items
+            .keys()
+            .filter(|x| !old.items.contains_key(*x))
+            .map(|x| item(new, x))
+            .collect();
+        let removed_items = old
+            .items
+            .keys()
+            .filter(|x| !new.items.contains_key(*x))
+            .map(|x| item(old, x))
+            .collect();
+        let renamed_items = old
+            .items
+            .iter()
+            .filter_map(|(mcid, old_name)| {
+                new.items.get(mcid).and_then(|new_name| {
+                    if new_name != old_name {
+                        Some(RenamedItem {
+                            minecraft_id: mcid.clone(),
+                            old_name: old_name.clone(),
+                            new_name: new_name.clone(),
+                        })
+                    } else {
+                        None
+                    }
+                })
+            }).collect();
+
+        // Pair up removed and added recipes that make the same things on the
+        // same machine, and call those changes. Extra copies of a recipe count
+        // as added or removed too.
+        let old_counts = old.recipe_counts();
+        let new_counts = new.recipe_counts();
+        let mut removed: BTreeMap<(String, Vec<String>), Vec<&SnapshotRecipe>> = BTreeMap::new();
+        for recipe in extra(&old_counts, &new_counts) {
+            removed
+                .entry(recipe.identity())
+                .or_insert_with(Vec::new)
+                .push(recipe);
+        }
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for recipe in extra(&new_counts, &old_counts) {
+            match removed.get_mut(&recipe.identity()).and_then(|x| x.pop()) {
+                Some(previous) => changed.push(ChangedRecipe {
+                    old: previous.clone(),
+                    new: recipe.clone(),
+                }),
+                None => added.push(recipe.clone()),
+            }
+        }
+        let removed: Vec<SnapshotRecipe> = removed
+            .into_iter()
+            .flat_map(|(_, x)| x.into_iter().cloned())
+            .collect();
+
+        // Group everything by machine, then by the first thing the recipe makes
+        let mut grouped: BTreeMap<String, BTreeMap<String, ItemDiff>> = BTreeMap::new();
+        for recipe in added {
+            item_diff(&mut grouped, old, new, &recipe).added.push(recipe);
+        }
+        for recipe in removed {
+            item_diff(&mut grouped, old, new, &recipe).removed.push(recipe);
+        }
+        for change in changed {
+            item_diff(&mut grouped, old, new, &change.new).changed.push(change);
+        }
+
+        PackDiff {
+            added_items,
+            removed_items,
+            renamed_items,
+            machines: grouped
+                .into_iter()
+                .map(|(machine, items)| MachineDiff {
+                    name: new
+                        .machines
+                        .get(&machine)
+                        .or_else(|| old.machines.get(&machine))
+                        .cloned()
+                        .unwrap_or_default(),
+                    machine,
+                    items: items.into_iter().map(|(_, x)| x).collect(),
+                }).collect(),
+        }
+    }
+
+    /// A changelog for reading on a web page
+    pub fn to_markdown(&self, old: &Snapshot, new: &Snapshot) -> String {
+        let mut out = String::from("# Pack changes\n\n");
+
+        let items = |out: &mut String, title: &str, items: &[DiffItem]| {
+            if items.is_empty() {
+                return;
+            }
+            writeln!(out, "## {}\n", title).unwrap();
+            for item in items {
+                writeln!(out, "- {} (`{}`)", item.name, item.minecraft_id).unwrap();
+            }
+            writeln!(out).unwrap();
+        };
+        items(&mut out, "New items", &self.added_items);
+        items(&mut out, "Removed items", &self.removed_items);
+
+        if !self.renamed_items.is_empty() {
+            writeln!(out, "## Renamed items\n").unwrap();
+            for item in &self.renamed_items {
+                writeln!(
+                    out,
+                    "- `{}`: {} → {}",
+                    item.minecraft_id, item.old_name, item.new_name
+                ).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        for machine in &self.machines {
+            writeln!(out, "## {} (`{}`)\n", machine.name, machine.machine).unwrap();
+            for item in &machine.items {
+                writeln!(out, "### {}\n", item.name).unwrap();
+                for recipe in &item.added {
+                    writeln!(out, "- Added: {}", describe_recipe(new, recipe)).unwrap();
+                }
+                for recipe in &item.removed {
+                    writeln!(out, "- Removed: {}", describe_recipe(old, recipe)).unwrap();
+                }
+                for change in &item.changed {
+                    writeln!(
+                        out,
+                        "- Changed: {}\n  - Now: {}",
+                        describe_recipe(old, &change.old),
+                        describe_recipe(new, &change.new)
+                    ).unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+        }
+
+        if self.added_items.is_empty()
+            && self.removed_items.is_empty()
+            && self.renamed_items.is_empty()
+            && self.machines.is_empty()
+        {
+            writeln!(out, "No changes.").unwrap();
+        }
+        out
+    }
+}
+
+/// The copies of recipes in `from` that `other` doesn't have
+fn extra<'a>(
+    from: &BTreeMap<&'a SnapshotRecipe, usize>,
+    other: &BTreeMap<&SnapshotRecipe, usize>,
+) -> Vec<&'a SnapshotRecipe> {
+    let mut extra = Vec::new();
+    for (recipe, count) in from {
+        let others = other.get(recipe).cloned().unwrap_or(0);
+        for _ in others..*count {
+            extra.push(*recipe);
+        }
+    }
+    extra
+}
+
+/// Find the entry for the item a recipe makes, creating it if needed
+fn item_diff<'a>(
+    grouped: &'a mut BTreeMap<String, BTreeMap<String, ItemDiff>>,
+    old: &Snapshot,
+    new: &Snapshot,
+    recipe: &SnapshotRecipe,
+) -> &'a mut ItemDiff {
+    let mcid = recipe
+        .outputs
+        .first()
+        .map(|x| x.minecraft_id.clone())
+        .unwrap_or_default();
+    let name = new
+        .items
+        .get(&mcid)
+        .or_else(|| old.items.get(&mcid))
+        .cloned()
+        .unwrap_or_default();
+    grouped
+        .entry(recipe.machine.clone())
+        .or_insert_with(BTreeMap::new)
+        .entry(mcid.clone())
+        .or_insert_with(|| ItemDiff {
+            minecraft_id: mcid,
+            name,
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        })
+}
+
+/// "2x Stick, Iron Ingot / Gold Ingot → 1x Pickaxe"
+fn describe_recipe(snapshot: &Snapshot, recipe: &SnapshotRecipe) -> String {
+    let stack = |x: &Stack| {
+        let name = snapshot
+            .items
+            .get(&x.minecraft_id)
+            .map(|x| x.as_str())
+            .unwrap_or(x.minecraft_id.as_str());
+        format!("{}x {}", x.quantity, name)
+    };
+    let inputs: Vec<String> = recipe
+        .inputs
+        .iter()
+        .map(|slot| slot.iter().map(&stack).collect::<Vec<_>>().join(" / "))
+        .collect();
+    let outputs: Vec<String> = recipe.outputs.iter().map(&stack).collect();
+    format!("{} → {}", inputs.join(", "), outputs.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(minecraft_id: &str, quantity: i32) -> Stack {
+        Stack {
+            minecraft_id: minecraft_id.to_owned(),
+            quantity,
+        }
+    }
+
+    fn snapshot(items: &[(&str, &str)], recipes: Vec<SnapshotRecipe>) -> Snapshot {
+        Snapshot {
+            items: items
+                .iter()
+                .map(|(mcid, name)| (mcid.to_string(), name.to_string()))
+                .collect(),
+            machines: vec![("minecraft.crafting".to_owned(), "Crafting".to_owned())]
+                .into_iter()
+                .collect(),
+            recipes,
+        }
+    }
+
+    fn planks(logs: i32, planks: i32) -> SnapshotRecipe {
+        SnapshotRecipe::new(
+            "minecraft.crafting".to_owned(),
+            vec![vec![stack("minecraft:log:0", logs)]],
+            vec![stack("minecraft:planks:0", planks)],
+        )
+    }
+
+    #[test]
+    fn finds_item_changes() {
+        let old = snapshot(&[("a", "Apple"), ("b", "Bread")], Vec::new());
+        let new = snapshot(&[("b", "Loaf"), ("c", "Cake")], Vec::new());
+        let diff = PackDiff::compute(&old, &new);
+
+        assert_eq!(diff.added_items.len(), 1);
+        assert_eq!(diff.added_items[0].minecraft_id, "c");
+        assert_eq!(diff.removed_items.len(), 1);
+        assert_eq!(diff.removed_items[0].name, "Apple");
+        assert_eq!(diff.renamed_items.len(), 1);
+        assert_eq!(diff.renamed_items[0].old_name, "Bread");
+        assert_eq!(diff.renamed_items[0].new_name, "Loaf");
+        assert!(diff.machines.is_empty());
+    }
+
+    #[test]
+    fn pairs_up_changed_recipes() {
+        let items = [("minecraft:planks:0", "Oak Wood Planks")];
+        let old = snapshot(&items, vec![planks(1, 4)]);
+        let new = snapshot(&items, vec![planks(1, 2)]);
+        let diff = PackDiff::compute(&old, &new);
+
+        assert_eq!(diff.machines.len(), 1);
+        let item = &diff.machines[0].items[0];
+        assert_eq!(item.name, "Oak Wood Planks");
+        assert!(item.added.is_empty() && item.removed.is_empty());
+        assert_eq!(item.changed.len(), 1);
+        assert_eq!(item.changed[0].old.outputs[0].quantity, 4);
+        assert_eq!(item.changed[0].new.outputs[0].quantity, 2);
+    }
+
+    #[test]
+    fn counts_copies_of_a_recipe() {
+        let old = snapshot(&[], vec![planks(1, 4), planks(1, 4)]);
+        let new = snapshot(&[], vec![planks(1, 4)]);
+
+        let diff = PackDiff::compute(&old, &new);
+        assert_eq!(diff.machines[0].items[0].removed, vec![planks(1, 4)]);
+
+        let diff = PackDiff::compute(&new, &old);
+        assert_eq!(diff.machines[0].items[0].added, vec![planks(1, 4)]);
+
+        assert!(PackDiff::compute(&old, &old).machines.is_empty());
+    }
+}
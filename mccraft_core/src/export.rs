@@ -0,0 +1,345 @@
+//! Turning bills of materials in to things people can paste somewhere else:
+//! spreadsheets, checklists and chat messages.
+
+use sql::ItemType;
+use std::collections::HashMap;
+use std::fmt::Write;
+use web::{Bom, BomItem, Recipe};
+
+/// How many items fit in a stack
+const STACK_SIZE: f64 = 64.0;
+/// How much fluid fits in a bucket, in mB
+const BUCKET_SIZE: f64 = 1000.0;
+/// Crafting trees deeper than this are cut off
+const MAX_TREE_DEPTH: usize = 32;
+
+/// The formats a bill of materials can be exported in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    Markdown,
+    Text,
+}
+
+impl Format {
+    /// Parse the name of a format, as given in a `format=` parameter
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "md" | "markdown" => Some(Format::Markdown),
+            "txt" | "text" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    /// Find the format that matches a MIME type, as given in an `Accept` header
+    pub fn from_mime(mime: &str) -> Option<Format> {
+        match mime.split(';').next().unwrap_or("").trim() {
+            "application/json" => Some(Format::Json),
+            "text/csv" => Some(Format::Csv),
+            "text/markdown" => Some(Format::Markdown),
+            "text/plain" => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match *self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Markdown => "text/markdown; charset=utf-8",
+            Format::Text => "text/plain; charset=utf-8",
+        }
+    }
+}
+
+/// Everything needed to describe a bill of materials to a person
+pub struct ShoppingList<'a> {
+    pub name: &'a str,
+    pub bom: &'a Bom,
+    /// What the BOM was computed for
+    pub targets: &'a [BomItem],
+    /// The recipe chosen for each item, by item ID
+    pub chosen: &'a HashMap<i32, Recipe>,
+}
+
+impl<'a> ShoppingList<'a> {
+    /// Render the list in the given format. JSON is left to the caller, since
+    /// it is just the BOM itself.
+    pub fn render(&self, format: Format) -> Option<String> {
+        match format {
+            Format::Json => None,
+            Format::Csv => Some(csv(self.bom)),
+            Format::Markdown => Some(self.markdown()),
+            Format::Text => Some(self.tree()),
+        }
+    }
+
+    /// A checklist of everything that needs to be gathered and crafted
+    pub fn markdown(&self) -> String {
+        let mut out = String::new();
+        if !self.name.is_empty() {
+            writeln!(out, "# {}\n", markdown_text(self.name)).unwrap();
+        }
+
+        writeln!(out, "## Gather\n").unwrap();
+        for item in &self.bom.raw {
+            writeln!(out, "- [ ] {}", markdown_text(&describe(item))).unwrap();
+        }
+
+        let recipes: HashMap<i32, &Recipe> =
+            self.chosen.values().map(|x| (x.recipe_id, x)).collect();
+        writeln!(out, "\n## Craft\n").unwrap();
+        for craft in &self.bom.crafts {
+            let recipe = match recipes.get(&craft.recipe_id) {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+            let outputs: Vec<String> = recipe
+                .outputs
+                .iter()
+                .map(|x| markdown_text(&x.item_name))
+                .collect();
+            writeln!(
+                out,
+                "- [ ] {} x {} ({})",
+                craft.crafts,
+                outputs.join(", "),
+                markdown_text(&recipe.machine.machine_name)
+            ).unwrap();
+        }
+
+        if !self.bom.tools.is_empty() {
+            writeln!(out, "\n## Tools\n").unwrap();
+            for item in &self.bom.tools {
+                writeln!(out, "- {}", markdown_text(&describe(item))).unwrap();
+            }
+        }
+
+        if !self.bom.leftovers.is_empty() {
+            writeln!(out, "\n## Leftovers\n").unwrap();
+            for item in &self.bom.leftovers {
+                writeln!(out, "- {}", markdown_text(&describe(item))).unwrap();
+            }
+        }
+
+        out
+    }
+
+    /// An indented tree showing what goes in to each target, for pasting in
+    /// to chat
+    pub fn tree(&self) -> String {
+        let mut out = String::new();
+        let mut path = Vec::new();
+        for target in self.targets {
+            self.tree_node(&mut out, target, true, &mut path);
+        }
+        out
+    }
+
+    fn tree_node(
+        &self,
+        out: &mut String,
+        item: &BomItem,
+        consumed: bool,
+        path: &mut Vec<i32>,
+    ) {
+        let indent = "  ".repeat(path.len());
+        let recipe = self.chosen.get(&item.item_id);
+        write!(out, "{}{}", indent, describe(item)).unwrap();
+        if let Some(recipe) = recipe {
+            write!(out, " [{}]", recipe.machine.machine_name).unwrap();
+        }
+        if !consumed {
+            write!(out, " (tool)").unwrap();
+        }
+        writeln!(out).unwrap();
+
+        let recipe = match recipe {
+            Some(recipe) if path.len() < MAX_TREE_DEPTH && !path.contains(&item.item_id) => {
+                recipe
+            }
+            _ => return,
+        };
+        let per_craft: i64 = recipe
+            .outputs
+            .iter()
+            .filter(|x| x.item_id == item.item_id)
+            .map(|x| x.quantity as i64)
+            .sum();
+        if per_craft <= 0 {
+            return;
+        }
+        let crafts = (item.quantity + per_craft - 1) / per_craft;
+
+        path.push(item.item_id);
+        for slot in &recipe.input_slots {
+            // Same choice as the BOM calculation: the first alternative we know
+            // how to make, or else the first one
+            let input = slot
+                .items
+                .iter()
+                .find(|x| self.chosen.contains_key(&x.item_id))
+                .or_else(|| slot.items.first());
+            if let Some(input) = input {
+                let needed = if slot.consumed {
+                    input.quantity as i64 * crafts
+                } else {
+                    input.quantity as i64
+                };
+                self.tree_node(out, &BomItem::new(input, needed), slot.consumed, path);
+            }
+        }
+        path.pop();
+    }
+}
+
+/// A spreadsheet of the raw materials in a BOM, with one row per item
+pub fn csv(bom: &Bom) -> String {
+    let mut out = String::from("item,mod,quantity,stacks,buckets\n");
+    for item in &bom.raw {
+        let (stacks, buckets) = match item.ty {
+            ItemType::Item => (format!("{:.2}", item.quantity as f64 / STACK_SIZE), String::new()),
+            ItemType::Fluid => (String::new(), format!("{:.3}", item.quantity as f64 / BUCKET_SIZE)),
+        };
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            csv_field(&item.item_name),
+            csv_field(mod_name(&item.minecraft_id)),
+            item.quantity,
+            stacks,
+            buckets
+        ).unwrap();
+    }
+    out
+}
+
+/// The mod an item comes from. Fluids don't say, so they get nothing.
+fn mod_name(minecraft_id: &str) -> &str {
+    match minecraft_id.find(':') {
+        Some(end) => &minecraft_id[..end],
+        None => "",
+    }
+}
+
+/// Quote a CSV field, if it needs it
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escape the characters Markdown would otherwise format, so item names like
+/// "*Special* Sword" come out as written
+fn markdown_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#|~".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Describe some amount of an item, e.g. "3 x Iron Ingot" or "250 mB Water"
+fn describe(item: &BomItem) -> String {
+    match item.ty {
+        ItemType::Item => format!("{} x {}", item.quantity, item.item_name),
+        ItemType::Fluid => format!("{} mB {}", item.quantity, item.item_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(item_id: i32, name: &str, minecraft_id: &str, ty: ItemType, quantity: i64) -> BomItem {
+        BomItem {
+            item_id,
+            item_name: name.to_owned(),
+            minecraft_id: minecraft_id.to_owned(),
+            ty,
+            quantity,
+        }
+    }
+
+    fn bom(raw: Vec<BomItem>, tools: Vec<BomItem>) -> Bom {
+        Bom {
+            raw,
+            crafts: Vec::new(),
+            leftovers: Vec::new(),
+            tools,
+        }
+    }
+
+    #[test]
+    fn csv_quotes_fields_that_need_it() {
+        assert_eq!(csv_field("Iron Ingot"), "Iron Ingot");
+        assert_eq!(csv_field("Stone, Smooth"), "\"Stone, Smooth\"");
+        assert_eq!(csv_field("12\" Pipe"), "\"12\"\" Pipe\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_has_a_row_per_raw_item() {
+        let bom = bom(
+            vec![
+                spec(1, "Cobblestone, Mossy", "minecraft:mossy_cobblestone:0", ItemType::Item, 96),
+                spec(2, "Water", "water", ItemType::Fluid, 2500),
+            ],
+            Vec::new(),
+        );
+        assert_eq!(
+            csv(&bom),
+            "item,mod,quantity,stacks,buckets\n\
+             \"Cobblestone, Mossy\",minecraft,96,1.50,\n\
+             Water,,2500,,2.500\n"
+        );
+    }
+
+    #[test]
+    fn quantities_past_i32_are_kept() {
+        let bom = bom(
+            vec![spec(1, "Cobblestone", "minecraft:cobblestone:0", ItemType::Item, 6_400_000_000)],
+            Vec::new(),
+        );
+        assert!(csv(&bom).contains(",6400000000,100000000.00,"));
+    }
+
+    #[test]
+    fn markdown_escapes_names() {
+        assert_eq!(markdown_text("*Special* [Sword]"), "\\*Special\\* \\[Sword\\]");
+        assert_eq!(markdown_text("Iron Ingot"), "Iron Ingot");
+
+        let bom = bom(
+            vec![spec(1, "__Cursed__ Stone", "mod:cursed:0", ItemType::Item, 2)],
+            vec![spec(2, "Hammer #1", "mod:hammer:0", ItemType::Item, 1)],
+        );
+        let chosen = HashMap::new();
+        let list = ShoppingList {
+            name: "My `plan`",
+            bom: &bom,
+            targets: &[],
+            chosen: &chosen,
+        };
+        let markdown = list.markdown();
+        assert!(markdown.starts_with("# My \\`plan\\`\n"));
+        assert!(markdown.contains("- [ ] 2 x \\_\\_Cursed\\_\\_ Stone\n"));
+        assert!(markdown.contains("## Tools\n\n- 1 x Hammer \\#1\n"));
+        assert!(!markdown.contains("## Leftovers"));
+    }
+
+    #[test]
+    fn formats_come_from_names_and_mime_types() {
+        assert_eq!(Format::from_name("md"), Some(Format::Markdown));
+        assert_eq!(Format::from_name("xlsx"), None);
+        assert_eq!(Format::from_mime("text/csv; charset=utf-8"), Some(Format::Csv));
+        assert_eq!(Format::from_mime("image/png"), None);
+    }
+}
@@ -0,0 +1,333 @@
+//! Crafting graphs, for rendering production chains with other tools.
+//!
+//! The graph is bipartite: item nodes point at the recipes they are used in,
+//! and recipe nodes point at the items they produce.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use web::{ItemSpec, Recipe};
+
+/// The formats a graph can be written in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz
+    Dot,
+    GraphMl,
+}
+
+impl GraphFormat {
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        match name {
+            "dot" | "gv" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match *self {
+            GraphFormat::Dot => "text/vnd.graphviz; charset=utf-8",
+            GraphFormat::GraphMl => "application/graphml+xml; charset=utf-8",
+        }
+    }
+}
+
+/// A node in a crafting graph
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum Node {
+    Item(i32),
+    Recipe(i32),
+}
+
+impl Node {
+    fn key(&self) -> String {
+        match *self {
+            Node::Item(id) => format!("item{}", id),
+            Node::Recipe(id) => format!("recipe{}", id),
+        }
+    }
+}
+
+/// An edge from an item to a recipe that uses it, or from a recipe to an item
+/// it makes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    pub quantity: i32,
+    /// False for tools and catalysts
+    pub consumed: bool,
+}
+
+/// A crafting graph
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    /// Item names, by item ID
+    pub items: BTreeMap<i32, String>,
+    /// Machine names, by recipe ID
+    pub recipes: BTreeMap<i32, String>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn contains_recipe(&self, recipe_id: i32) -> bool {
+        self.recipes.contains_key(&recipe_id)
+    }
+
+    /// The number of nodes in the graph
+    pub fn len(&self) -> usize {
+        self.items.len() + self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.recipes.is_empty()
+    }
+
+    /// Add a recipe, its outputs and the inputs `choose` picks from each of
+    /// its slots. Returns the IDs of the inputs that were added.
+    pub fn add_recipe<F>(&mut self, recipe: &Recipe, mut choose: F) -> Vec<i32>
+    where
+        F: FnMut(&[ItemSpec]) -> Option<usize>,
+    {
+        if self.contains_recipe(recipe.recipe_id) {
+            return Vec::new();
+        }
+        let node = Node::Recipe(recipe.recipe_id);
+        self.recipes
+            .insert(recipe.recipe_id, recipe.machine.machine_name.clone());
+
+        for output in &recipe.outputs {
+            self.items
+                .insert(output.item_id, output.item_name.clone());
+            self.edges.push(Edge {
+                from: node,
+                to: Node::Item(output.item_id),
+                quantity: output.quantity,
+                consumed: true,
+            });
+        }
+
+        let mut inputs = Vec::new();
+        for slot in &recipe.input_slots {
+            let input = match choose(&slot.items).and_then(|i| slot.items.get(i)) {
+                Some(input) => input,
+                None => continue,
+            };
+            self.items.insert(input.item_id, input.item_name.clone());
+            self.edges.push(Edge {
+                from: Node::Item(input.item_id),
+                to: node,
+                quantity: input.quantity,
+                consumed: slot.consumed,
+            });
+            inputs.push(input.item_id);
+        }
+
+        inputs
+    }
+
+    pub fn render(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.to_dot(),
+            GraphFormat::GraphMl => self.to_graphml(),
+        }
+    }
+
+    /// Write the graph in Graphviz's DOT language
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph crafting {\n    rankdir=LR;\n");
+        for (id, name) in &self.items {
+            writeln!(
+                out,
+                "    {} [label={}, shape=ellipse];",
+                Node::Item(*id).key(),
+                dot_string(name)
+            ).unwrap();
+        }
+        for (id, machine) in &self.recipes {
+            writeln!(
+                out,
+                "    {} [label={}, shape=box];",
+                Node::Recipe(*id).key(),
+                dot_string(machine)
+            ).unwrap();
+        }
+        for edge in &self.edges {
+            write!(
+                out,
+                "    {} -> {} [label=\"{}\"",
+                edge.from.key(),
+                edge.to.key(),
+                edge.quantity
+            ).unwrap();
+            if !edge.consumed {
+                out.push_str(", style=dashed");
+            }
+            out.push_str("];\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Write the graph as GraphML
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"quantity\" for=\"edge\" attr.name=\"quantity\" attr.type=\"int\"/>\n",
+            "  <key id=\"consumed\" for=\"edge\" attr.name=\"consumed\" attr.type=\"boolean\"/>\n",
+            "  <graph id=\"crafting\" edgedefault=\"directed\">\n",
+        ));
+        let nodes = self
+            .items
+            .iter()
+            .map(|(id, name)| (Node::Item(*id), name, "item"))
+            .chain(
+                self.recipes
+                    .iter()
+                    .map(|(id, machine)| (Node::Recipe(*id), machine, "recipe")),
+            );
+        for (node, label, kind) in nodes {
+            writeln!(
+                out,
+                "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"kind\">{}</data></node>",
+                node.key(),
+                xml_escape(label),
+                kind
+            ).unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"quantity\">{}</data><data key=\"consumed\">{}</data></edge>",
+                edge.from.key(),
+                edge.to.key(),
+                edge.quantity,
+                edge.consumed
+            ).unwrap();
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+/// Quote a string for use in DOT
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sql::ItemType;
+    use web::{InputSlot, Machine};
+
+    fn spec(item_id: i32, name: &str, quantity: i32) -> ItemSpec {
+        ItemSpec {
+            item_id,
+            item_name: name.to_owned(),
+            minecraft_id: format!("test:item{}:0", item_id),
+            ty: ItemType::Item,
+            quantity,
+        }
+    }
+
+    /// A pickaxe from 3 planks "& more", with a crafting hammer that isn't
+    /// used up
+    fn graph() -> Graph {
+        let recipe = Recipe {
+            recipe_id: 7,
+            recipe_hash: None,
+            machine: Machine {
+                machine_id: 1,
+                machine_name: "\"Crafting\" <Table>".to_owned(),
+                minecraft_id: "minecraft.crafting".to_owned(),
+                bg_texture: String::new(),
+                catalysts: Vec::new(),
+            },
+            input_slots: vec![
+                InputSlot {
+                    items: vec![spec(1, "Planks & more", 3)],
+                    group: None,
+                    consumed: true,
+                },
+                InputSlot {
+                    items: vec![spec(2, "Hammer", 1)],
+                    group: None,
+                    consumed: false,
+                },
+            ],
+            outputs: vec![spec(3, "Pickaxe", 1)],
+        };
+
+        let mut graph = Graph::new();
+        assert_eq!(graph.add_recipe(&recipe, |_| Some(0)), vec![1, 2]);
+        // Adding it again changes nothing
+        assert!(graph.add_recipe(&recipe, |_| Some(0)).is_empty());
+        graph
+    }
+
+    #[test]
+    fn builds_a_bipartite_graph() {
+        let graph = graph();
+        assert_eq!(graph.len(), 4);
+        assert!(graph.contains_recipe(7));
+        assert_eq!(graph.edges.len(), 3);
+        assert_eq!(graph.edges[0].from, Node::Recipe(7));
+        assert_eq!(graph.edges[0].to, Node::Item(3));
+        assert_eq!(graph.edges[2].from, Node::Item(2));
+        assert!(!graph.edges[2].consumed);
+    }
+
+    #[test]
+    fn writes_dot() {
+        assert_eq!(
+            graph().to_dot(),
+            "digraph crafting {\n    rankdir=LR;\n    \
+             item1 [label=\"Planks & more\", shape=ellipse];\n    \
+             item2 [label=\"Hammer\", shape=ellipse];\n    \
+             item3 [label=\"Pickaxe\", shape=ellipse];\n    \
+             recipe7 [label=\"\\\"Crafting\\\" <Table>\", shape=box];\n    \
+             recipe7 -> item3 [label=\"1\"];\n    \
+             item1 -> recipe7 [label=\"3\"];\n    \
+             item2 -> recipe7 [label=\"1\", style=dashed];\n}\n"
+        );
+    }
+
+    #[test]
+    fn writes_graphml() {
+        let graphml = graph().to_graphml();
+        assert!(graphml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml"));
+        assert!(graphml.contains(
+            "<node id=\"item1\"><data key=\"label\">Planks &amp; more</data><data key=\"kind\">item</data></node>"
+        ));
+        assert!(graphml.contains(
+            "<node id=\"recipe7\"><data key=\"label\">&quot;Crafting&quot; &lt;Table&gt;</data><data key=\"kind\">recipe</data></node>"
+        ));
+        assert!(graphml.contains(
+            "<edge source=\"item2\" target=\"recipe7\"><data key=\"quantity\">1</data><data key=\"consumed\">false</data></edge>"
+        ));
+        assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+    }
+
+    #[test]
+    fn formats_come_from_names() {
+        assert_eq!(GraphFormat::from_name("gv"), Some(GraphFormat::Dot));
+        assert_eq!(GraphFormat::from_name("graphml"), Some(GraphFormat::GraphMl));
+        assert_eq!(GraphFormat::from_name("svg"), None);
+    }
+}
@@ -1,3 +1,6 @@
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BackgroundImage {
     #[serde(rename = "w")]
@@ -56,7 +59,173 @@ pub struct Recipe {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CraftingInstance {
+    /// The UID of the JEI category. Older versions of the exporter only encode
+    /// this in the file name.
+    #[serde(default)]
+    pub uid: Option<String>,
     pub category: String,
     pub bg: BackgroundImage,
     pub recipes: Vec<Recipe>,
+    /// The blocks that can perform recipes in this category. Older versions of
+    /// the exporter don't write these.
+    #[serde(default, deserialize_with = "super::deser_skip_nulls_list")]
+    pub catalysts: Vec<ItemStack>,
+}
+
+/// Everything in a `CraftingInstance` other than its recipes
+#[derive(Debug)]
+pub struct CategoryInfo {
+    pub uid: Option<String>,
+    pub category: String,
+    pub bg: BackgroundImage,
+    pub catalysts: Vec<ItemStack>,
+}
+
+/// Deserializes a `CraftingInstance` one recipe at a time, handing each recipe
+/// to `on_recipe` as soon as it's read. The category files for big packs can
+/// be hundreds of megabytes, so only one recipe at a time is held in its JSON
+/// form; whatever `on_recipe` keeps of them is up to it. The recipes can come
+/// before the rest of the instance in the file.
+pub struct StreamingInstance<F> {
+    pub on_recipe: F,
+}
+
+impl<'de, F: FnMut(Recipe)> DeserializeSeed<'de> for StreamingInstance<F> {
+    type Value = CategoryInfo;
+
+    fn deserialize<D: Deserializer<'de>>(self, deser: D) -> Result<CategoryInfo, D::Error> {
+        deser.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(Recipe)> Visitor<'de> for StreamingInstance<F> {
+    type Value = CategoryInfo;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a crafting instance")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<CategoryInfo, A::Error> {
+        /// Lets `catalysts` skip nulls the same way it does in `CraftingInstance`
+        #[derive(Deserialize)]
+        struct Catalysts(#[serde(deserialize_with = "super::deser_skip_nulls_list")] Vec<ItemStack>);
+
+        let mut uid = None;
+        let mut category = None;
+        let mut bg = None;
+        let mut catalysts = Vec::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "uid" => uid = map.next_value()?,
+                "category" => category = Some(map.next_value()?),
+                "bg" => bg = Some(map.next_value()?),
+                "catalysts" => catalysts = map.next_value::<Catalysts>()?.0,
+                "recipes" => map.next_value_seed(RecipeSeq {
+                    on_recipe: &mut self.on_recipe,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(CategoryInfo {
+            uid,
+            category: category.ok_or_else(|| de::Error::missing_field("category"))?,
+            bg: bg.ok_or_else(|| de::Error::missing_field("bg"))?,
+            catalysts,
+        })
+    }
+}
+
+/// The `recipes` list of a `StreamingInstance`
+struct RecipeSeq<'a, F: 'a> {
+    on_recipe: &'a mut F,
+}
+
+impl<'a, 'de, F: FnMut(Recipe)> DeserializeSeed<'de> for RecipeSeq<'a, F> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deser: D) -> Result<(), D::Error> {
+        deser.deserialize_seq(self)
+    }
+}
+
+impl<'a, 'de, F: FnMut(Recipe)> Visitor<'de> for RecipeSeq<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a sequence of recipes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(recipe) = seq.next_element::<Recipe>()? {
+            (self.on_recipe)(recipe);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    const RECIPES: &str = r#"[
+        {"ingredientItems": [
+            {"x": 0, "y": 0, "w": 16, "h": 16, "p": 0, "in": true,
+             "stacks": [{"amount": 1, "type": "minecraft:log:0"}, null]},
+            {"x": 60, "y": 0, "w": 16, "h": 16, "p": 0, "in": false,
+             "stacks": [{"amount": 4, "type": "minecraft:planks:0"}]}
+        ], "ingredientFluids": []},
+        {"ingredientItems": [], "ingredientFluids": [
+            {"x": 0, "y": 0, "w": 16, "h": 16, "p": 0, "in": true,
+             "fluids": [{"amount": 1000, "type": "water"}]}
+        ]}
+    ]"#;
+
+    fn stream(json: &str) -> (CategoryInfo, Vec<Recipe>) {
+        let mut recipes = Vec::new();
+        let info = {
+            let mut deser = serde_json::Deserializer::from_str(json);
+            let info = StreamingInstance {
+                on_recipe: |recipe| recipes.push(recipe),
+            }.deserialize(&mut deser)
+            .unwrap();
+            deser.end().unwrap();
+            info
+        };
+        (info, recipes)
+    }
+
+    fn assert_same(json: &str) {
+        let whole: CraftingInstance = serde_json::from_str(json).unwrap();
+        let (info, recipes) = stream(json);
+        assert_eq!(info.uid, whole.uid);
+        assert_eq!(info.category, whole.category);
+        assert_eq!(info.bg.tex, whole.bg.tex);
+        assert_eq!(format!("{:?}", info.catalysts), format!("{:?}", whole.catalysts));
+        assert_eq!(format!("{:?}", recipes), format!("{:?}", whole.recipes));
+        assert_eq!(recipes.len(), 2);
+    }
+
+    #[test]
+    fn streams_the_same_instance() {
+        assert_same(&format!(
+            r#"{{"uid": "minecraft.crafting", "category": "Crafting",
+                "bg": {{"w": 116, "h": 54, "tex": "crafting.png"}},
+                "catalysts": [null, {{"amount": 1, "type": "minecraft:crafting_table:0"}}],
+                "recipes": {}}}"#,
+            RECIPES
+        ));
+    }
+
+    #[test]
+    fn streams_recipes_before_the_category() {
+        assert_same(&format!(
+            r#"{{"recipes": {}, "category": "Smelting",
+                "bg": {{"w": 82, "h": 54, "tex": "furnace.png"}}}}"#,
+            RECIPES
+        ));
+    }
 }
@@ -2,11 +2,30 @@
 
 #[macro_use] extern crate serde_derive;
 extern crate serde;
+extern crate sha2;
 #[macro_use] extern crate diesel;
 #[macro_use] extern crate diesel_derive_enum;
+#[cfg(test)]
+extern crate serde_json;
 
+/// Health checks for imported recipe databases
+pub mod analysis;
+/// Bill of materials calculations
+pub mod bom;
+/// Detection of recipes that take the same inputs
+pub mod conflicts;
+/// Changelogs between two versions of a pack
+pub mod diff;
+/// Exporting bills of materials as spreadsheets, checklists and text
+pub mod export;
+/// Crafting graphs in formats other tools understand
+pub mod graph;
 /// The JSON schema for representing recipes
 pub mod json;
+/// Parsing of the item IDs produced by the JEI exporter
+pub mod minecraft_id;
+/// Hashes that identify a recipe across imports
+pub mod recipe_hash;
 /// The SQL schema for representing recipes
 #[allow(unused_imports)]
 pub mod schema;
@@ -0,0 +1,135 @@
+//! Parsing for the item IDs produced by the JEI exporter.
+//!
+//! Item stacks are written as `<mod>:<name>:<meta>`, optionally followed by
+//! another `:` and the NBT data attached to the stack. Fluids are written as a
+//! bare fluid name.
+
+/// The pieces of an exporter item ID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinecraftId<'a> {
+    /// The registry name of the item, without any variant information
+    pub base_id: &'a str,
+    /// The damage value/metadata of the item, if the ID has one
+    pub meta: Option<i32>,
+    /// A stable hash of the NBT tag attached to the item, if the ID has one
+    pub nbt_hash: Option<String>,
+}
+
+impl<'a> MinecraftId<'a> {
+    /// Split an exporter ID into its base ID, metadata and NBT hash.
+    ///
+    /// IDs that don't follow the `<mod>:<name>:<meta>` pattern (fluids, mostly)
+    /// are returned as a base ID with no variant information.
+    pub fn parse(id: &'a str) -> Self {
+        let mut colons = id.match_indices(':').map(|(i, _)| i);
+        let base_end = match (colons.next(), colons.next()) {
+            (Some(_), Some(end)) => end,
+            _ => {
+                return MinecraftId {
+                    base_id: id,
+                    meta: None,
+                    nbt_hash: None,
+                }
+            }
+        };
+
+        let (meta, nbt) = match colons.next() {
+            Some(meta_end) => (&id[base_end + 1..meta_end], Some(&id[meta_end + 1..])),
+            None => (&id[base_end + 1..], None),
+        };
+
+        let meta = match meta.parse::<i32>() {
+            Ok(meta) => meta,
+            // Not something we know how to split up, so don't try
+            Err(_) => {
+                return MinecraftId {
+                    base_id: id,
+                    meta: None,
+                    nbt_hash: None,
+                }
+            }
+        };
+
+        MinecraftId {
+            base_id: &id[..base_end],
+            meta: Some(meta),
+            nbt_hash: nbt.filter(|x| !x.is_empty()).map(hash_nbt),
+        }
+    }
+}
+
+/// Reduce an NBT string to a short identifier. The exporter sometimes writes a
+/// hash of the tag instead of the tag itself, in which case we keep it as is.
+fn hash_nbt(nbt: &str) -> String {
+    if nbt.len() <= 16 && nbt.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return nbt.to_string();
+    }
+
+    // 64-bit FNV-1a. We need something that is stable across runs and Rust
+    // versions, which rules out the standard library hashers.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in nbt.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_meta() {
+        assert_eq!(
+            MinecraftId::parse("minecraft:wool:14"),
+            MinecraftId {
+                base_id: "minecraft:wool",
+                meta: Some(14),
+                nbt_hash: None,
+            }
+        );
+        assert_eq!(MinecraftId::parse("minecraft:stone:-1").meta, Some(-1));
+    }
+
+    #[test]
+    fn parses_nbt() {
+        let id = MinecraftId::parse("minecraft:potion:0:{Potion:\"minecraft:swiftness\"}");
+        assert_eq!(id.base_id, "minecraft:potion");
+        assert_eq!(id.meta, Some(0));
+        assert_eq!(id.nbt_hash, Some(hash_nbt("{Potion:\"minecraft:swiftness\"}")));
+
+        // An empty tag is the same as no tag
+        assert_eq!(MinecraftId::parse("minecraft:potion:0:").nbt_hash, None);
+    }
+
+    #[test]
+    fn leaves_other_ids_alone() {
+        for id in &["water", "minecraft:stone", "mod:thing:notmeta", "mod:thing:99999999999"] {
+            assert_eq!(
+                MinecraftId::parse(id),
+                MinecraftId {
+                    base_id: id,
+                    meta: None,
+                    nbt_hash: None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_short_hashes() {
+        assert_eq!(hash_nbt("1a2b-3c4d"), "1a2b-3c4d");
+    }
+
+    #[test]
+    fn hashes_long_tags() {
+        let tag = "{display:{Name:\"Sword of Testing\"}}";
+        let hash = hash_nbt(tag);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, hash_nbt(tag));
+        assert_ne!(hash, hash_nbt("{display:{Name:\"Sword of Texting\"}}"));
+        assert_eq!(hash_nbt("{}"), "08f44b07b5901a25");
+    }
+}
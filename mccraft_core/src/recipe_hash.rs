@@ -0,0 +1,47 @@
+//! Content hashes for recipes.
+//!
+//! The hash covers the machine, the alternatives in each slot (in order) and
+//! the outputs. It stays the same across imports, so it can be used to refer
+//! to a recipe from outside the database. Whether inputs are consumed and how
+//! slots are grouped are decided after the fact, so they aren't included.
+
+use sha2::{Digest, Sha256};
+
+/// An input or output, as far as the hash is concerned
+#[derive(Debug, Clone, Copy)]
+pub struct HashedStack<'a> {
+    pub fluid: bool,
+    /// Minecraft ID of the item or fluid
+    pub name: &'a str,
+    pub quantity: i32,
+}
+
+/// Compute the content hash of a recipe
+pub fn content_hash(machine: &str, inputs: &[Vec<HashedStack>], outputs: &[HashedStack]) -> String {
+    fn stack(hasher: &mut Sha256, stack: &HashedStack) {
+        let kind = if stack.fluid { "fluid" } else { "item" };
+        hasher.input(format!("{} {} {}\n", kind, stack.name, stack.quantity).as_bytes());
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.input(format!("machine {}\n", machine).as_bytes());
+    for slot in inputs {
+        hasher.input(b"slot\n");
+        // The order of alternatives within a slot doesn't matter
+        let mut elements = slot.clone();
+        elements.sort_by_key(|x| (x.name, x.quantity));
+        for element in &elements {
+            stack(&mut hasher, element);
+        }
+    }
+    hasher.input(b"outputs\n");
+    for output in outputs {
+        stack(&mut hasher, output);
+    }
+
+    hasher
+        .result()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
@@ -5,6 +5,34 @@ pub mod mccraft {
             crafting_slot -> Int4,
             item -> Int4,
             quantity -> Int4,
+            consumed -> Bool,
+        }
+    }
+
+    table! {
+        mccraft.fluid_containers (id) {
+            id -> Int4,
+            filled_item -> Int4,
+            fluid -> Int4,
+            amount -> Int4,
+            empty_item -> Nullable<Int4>,
+        }
+    }
+
+    table! {
+        mccraft.ingredient_group_members (id) {
+            id -> Int4,
+            ingredient_group -> Int4,
+            item -> Int4,
+            quantity -> Int4,
+        }
+    }
+
+    table! {
+        mccraft.ingredient_groups (id) {
+            id -> Int4,
+            human_name -> Text,
+            signature -> Text,
         }
     }
 
@@ -12,6 +40,9 @@ pub mod mccraft {
         mccraft.input_slots (id) {
             id -> Int4,
             for_recipe -> Int4,
+            ingredient_group -> Nullable<Int4>,
+            x -> Nullable<Int4>,
+            y -> Nullable<Int4>,
         }
     }
 
@@ -24,6 +55,18 @@ pub mod mccraft {
             ty -> ItemTypeMapping,
             human_name -> Text,
             minecraft_id -> Text,
+            base_id -> Text,
+            meta -> Nullable<Int4>,
+            nbt_hash -> Nullable<Text>,
+            raw -> Bool,
+        }
+    }
+
+    table! {
+        mccraft.machine_catalysts (id) {
+            id -> Int4,
+            machine -> Int4,
+            item -> Int4,
         }
     }
 
@@ -32,6 +75,7 @@ pub mod mccraft {
             id -> Int4,
             human_name -> Text,
             minecraft_id -> Text,
+            bg_texture -> Text,
         }
     }
 
@@ -44,26 +88,70 @@ pub mod mccraft {
         }
     }
 
+    table! {
+        mccraft.plan_recipes (id) {
+            id -> Int4,
+            plan -> Int4,
+            item -> Int4,
+            recipe_hash -> Text,
+        }
+    }
+
+    table! {
+        mccraft.plan_targets (id) {
+            id -> Int4,
+            plan -> Int4,
+            item -> Int4,
+            quantity -> Int4,
+        }
+    }
+
+    table! {
+        mccraft.plans (id) {
+            id -> Int4,
+            slug -> Text,
+            name -> Text,
+        }
+    }
+
     table! {
         mccraft.recipes (id) {
             id -> Int4,
             machine -> Int4,
+            content_hash -> Nullable<Text>,
+            hidden -> Bool,
         }
     }
 
     joinable!(crafting_components -> input_slots (crafting_slot));
     joinable!(crafting_components -> items (item));
+    joinable!(ingredient_group_members -> ingredient_groups (ingredient_group));
+    joinable!(ingredient_group_members -> items (item));
+    joinable!(input_slots -> ingredient_groups (ingredient_group));
     joinable!(input_slots -> recipes (for_recipe));
+    joinable!(machine_catalysts -> items (item));
+    joinable!(machine_catalysts -> machines (machine));
     joinable!(outputs -> items (item));
     joinable!(outputs -> recipes (recipe));
+    joinable!(plan_recipes -> items (item));
+    joinable!(plan_recipes -> plans (plan));
+    joinable!(plan_targets -> items (item));
+    joinable!(plan_targets -> plans (plan));
     joinable!(recipes -> machines (machine));
 
     allow_tables_to_appear_in_same_query!(
         crafting_components,
+        fluid_containers,
+        ingredient_group_members,
+        ingredient_groups,
         input_slots,
         items,
+        machine_catalysts,
         machines,
         outputs,
+        plan_recipes,
+        plan_targets,
+        plans,
         recipes,
     );
 }
@@ -6,12 +6,17 @@ pub enum ItemType {
     Fluid,
 }
 
-#[derive(Serialize, Identifiable, Queryable, Debug)]
+#[derive(Serialize, Deserialize, Identifiable, Queryable, Debug, Clone)]
 pub struct Item {
     pub id: i32,
     pub ty: ItemType,
     pub human_name: String,
     pub minecraft_id: String,
+    pub base_id: String,
+    pub meta: Option<i32>,
+    pub nbt_hash: Option<String>,
+    /// Whether the item is gathered rather than crafted
+    pub raw: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -20,13 +25,18 @@ pub struct NewItem<'a> {
     pub human_name: &'a str,
     pub minecraft_id: &'a str,
     pub ty: ItemType,
+    pub base_id: &'a str,
+    pub meta: Option<i32>,
+    pub nbt_hash: Option<String>,
 }
 
 #[derive(Identifiable, Queryable, PartialEq, Eq, Debug)]
 pub struct Machine {
     pub id: i32,
     pub human_name: String,
+    /// The UID of the JEI category
     pub minecraft_id: String,
+    pub bg_texture: String,
 }
 
 #[derive(Insertable, Debug)]
@@ -34,6 +44,23 @@ pub struct Machine {
 pub struct NewMachine<'a> {
     pub human_name: &'a str,
     pub minecraft_id: &'a str,
+    pub bg_texture: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
+#[belongs_to(Machine, foreign_key = "machine")]
+#[belongs_to(Item, foreign_key = "item")]
+pub struct MachineCatalyst {
+    pub id: i32,
+    pub machine: i32,
+    pub item: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "machine_catalysts"]
+pub struct NewMachineCatalyst {
+    pub machine: i32,
+    pub item: i32,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
@@ -41,12 +68,18 @@ pub struct NewMachine<'a> {
 pub struct Recipe {
     pub id: i32,
     pub machine: i32,
+    /// A hash of the recipe's machine, inputs and outputs, which stays the
+    /// same across imports
+    pub content_hash: Option<String>,
+    /// Hidden recipes are left out of everything the web server returns
+    pub hidden: bool,
 }
 
 #[derive(Insertable, Debug)]
 #[table_name = "recipes"]
-pub struct NewRecipe {
+pub struct NewRecipe<'a> {
     pub machine: i32,
+    pub content_hash: &'a str,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
@@ -69,15 +102,22 @@ pub struct NewOutput {
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
 #[belongs_to(Recipe, foreign_key = "for_recipe")]
+#[belongs_to(IngredientGroup, foreign_key = "ingredient_group")]
 pub struct InputSlot {
     pub id: i32,
     pub for_recipe: i32,
+    pub ingredient_group: Option<i32>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
 }
 
 #[derive(Insertable, Debug)]
 #[table_name = "input_slots"]
 pub struct NewInputSlot {
     pub for_recipe: i32,
+    pub ingredient_group: Option<i32>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
 }
 
 #[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
@@ -86,8 +126,9 @@ pub struct NewInputSlot {
 pub struct CraftingComponent {
     pub id: i32,
     pub crafting_slot: i32,
-    pub quantity: i32,
     pub item: i32,
+    pub quantity: i32,
+    pub consumed: bool,
 }
 
 #[derive(Insertable, Debug)]
@@ -96,4 +137,106 @@ pub struct NewCraftingComponent {
     pub crafting_slot: i32,
     pub quantity: i32,
     pub item: i32,
+    pub consumed: bool,
+}
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug)]
+pub struct IngredientGroup {
+    pub id: i32,
+    pub human_name: String,
+    pub signature: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "ingredient_groups"]
+pub struct NewIngredientGroup<'a> {
+    pub human_name: &'a str,
+    pub signature: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
+#[belongs_to(IngredientGroup, foreign_key = "ingredient_group")]
+#[belongs_to(Item, foreign_key = "item")]
+pub struct IngredientGroupMember {
+    pub id: i32,
+    pub ingredient_group: i32,
+    pub item: i32,
+    pub quantity: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "ingredient_group_members"]
+pub struct NewIngredientGroupMember {
+    pub ingredient_group: i32,
+    pub item: i32,
+    pub quantity: i32,
+}
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug)]
+pub struct FluidContainer {
+    pub id: i32,
+    pub filled_item: i32,
+    pub fluid: i32,
+    pub amount: i32,
+    pub empty_item: Option<i32>,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "fluid_containers"]
+pub struct NewFluidContainer {
+    pub filled_item: i32,
+    pub fluid: i32,
+    pub amount: i32,
+    pub empty_item: Option<i32>,
+}
+
+#[derive(Identifiable, Queryable, PartialEq, Eq, Debug)]
+pub struct Plan {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "plans"]
+pub struct NewPlan<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
+#[belongs_to(Plan, foreign_key = "plan")]
+#[belongs_to(Item, foreign_key = "item")]
+pub struct PlanTarget {
+    pub id: i32,
+    pub plan: i32,
+    pub item: i32,
+    pub quantity: i32,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "plan_targets"]
+pub struct NewPlanTarget {
+    pub plan: i32,
+    pub item: i32,
+    pub quantity: i32,
+}
+
+#[derive(Identifiable, Queryable, Associations, PartialEq, Eq, Debug)]
+#[belongs_to(Plan, foreign_key = "plan")]
+#[belongs_to(Item, foreign_key = "item")]
+pub struct PlanRecipe {
+    pub id: i32,
+    pub plan: i32,
+    pub item: i32,
+    /// The content hash of the chosen recipe
+    pub recipe_hash: String,
+}
+
+#[derive(Insertable, Debug)]
+#[table_name = "plan_recipes"]
+pub struct NewPlanRecipe<'a> {
+    pub plan: i32,
+    pub item: i32,
+    pub recipe_hash: &'a str,
 }
@@ -1,6 +1,7 @@
 //! Types for use when communicating between the web server and frontend
 
-use sql::ItemType;
+use graph::Edge;
+use sql::{self, ItemType};
 
 /// Specifies an input or output item
 #[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
@@ -17,21 +18,74 @@ pub struct ItemSpec {
     pub quantity: i32,
 }
 
+/// A named set of alternatives that is shared between many recipes (for
+/// example, "any Wood Planks")
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IngredientGroup {
+    pub group_id: i32,
+    pub group_name: String,
+}
+
 /// An input item slot
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct InputSlot {
     pub items: Vec<ItemSpec>,
+    /// The ingredient group this slot accepts, if any. When present, `items`
+    /// holds the members of the group.
+    pub group: Option<IngredientGroup>,
+    /// Whether the recipe uses up whatever is in this slot. Tools and catalysts
+    /// are not consumed.
+    pub consumed: bool,
 }
 
-/// The data required to complete a PartialRecipe
+/// Everything there is to know about a recipe
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Recipe {
+    pub recipe_id: i32,
+    /// An identifier for the recipe that stays the same across imports. This
+    /// can be used anywhere a recipe ID can.
+    pub recipe_hash: Option<String>,
+    /// The machine that performs the recipe
+    pub machine: Machine,
     /// The inputs to the recipe
     pub input_slots: Vec<InputSlot>,
     /// The things produced by this recipe
     pub outputs: Vec<ItemSpec>,
 }
 
+/// Everything we know about a machine
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Machine {
+    pub machine_id: i32,
+    pub machine_name: String,
+    /// The UID of the JEI category
+    pub minecraft_id: String,
+    /// The texture JEI draws behind the recipes
+    pub bg_texture: String,
+    /// The items (usually blocks) that can perform this machine's recipes
+    pub catalysts: Vec<ItemSpec>,
+}
+
+/// A machine, and how many recipes it has
+#[derive(Serialize, Deserialize, Queryable, Debug, Clone)]
+pub struct MachineSummary {
+    pub machine_id: i32,
+    pub machine_name: String,
+    pub minecraft_id: String,
+    pub bg_texture: String,
+    pub recipe_count: i64,
+}
+
+/// One page of a longer list of recipes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecipePage {
+    /// How many recipes there are in total
+    pub total: i64,
+    /// Where this page starts in the full list
+    pub offset: i64,
+    pub recipes: Vec<Recipe>,
+}
+
 /// A partial recipe. For when we only care about the fact that a particular
 /// machine can make a given thing.
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,3 +94,220 @@ pub struct PartialRecipe {
     pub machine_id: i32,
     pub recipe_id: i32,
 }
+
+/// A quantity of some item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemQuantity {
+    pub item_id: i32,
+    pub quantity: i32,
+}
+
+/// A reference to a recipe, either by its internal ID # or by its content
+/// hash. Unlike the ID, the hash survives a re-import.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum RecipeRef {
+    Id(i32),
+    Hash(String),
+}
+
+impl RecipeRef {
+    /// Interpret a string from a URL as a recipe reference. Anything that
+    /// looks like a number is taken to be an ID.
+    pub fn parse(s: &str) -> Self {
+        match s.parse::<i32>() {
+            Ok(id) => RecipeRef::Id(id),
+            Err(_) => RecipeRef::Hash(s.to_string()),
+        }
+    }
+}
+
+/// The recipe that should be used to make some item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecipeChoice {
+    pub item_id: i32,
+    pub recipe_id: RecipeRef,
+}
+
+/// A request to compute a bill of materials
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BomRequest {
+    /// What we want to end up with
+    pub targets: Vec<ItemQuantity>,
+    /// The recipe to use for each intermediate item. Items without a recipe
+    /// are considered raw materials.
+    pub recipes: Vec<RecipeChoice>,
+    /// Whether the machines that perform the recipes need to be built too
+    #[serde(default)]
+    pub include_machines: bool,
+}
+
+/// The contents of a crafting plan, as sent by the client when saving it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanContents {
+    #[serde(default)]
+    pub name: String,
+    /// What the plan is meant to make
+    pub targets: Vec<ItemQuantity>,
+    /// The recipe chosen for each intermediate item
+    pub recipes: Vec<RecipeChoice>,
+}
+
+/// A saved crafting plan. Recipes are always referred to by their hash, so
+/// that the plan keeps working after a re-import.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Plan {
+    /// The random identifier used to share the plan
+    pub slug: String,
+    pub name: String,
+    pub targets: Vec<ItemQuantity>,
+    pub recipes: Vec<RecipeChoice>,
+}
+
+/// A node in a crafting tree
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TreeNode {
+    Item { item_id: i32, item_name: String },
+    Recipe { recipe_id: i32, machine_name: String },
+}
+
+/// Everything that goes in to making an item, down to some depth
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CraftingTree {
+    /// The item the tree is for
+    pub root: i32,
+    pub nodes: Vec<TreeNode>,
+    pub edges: Vec<Edge>,
+    /// Every recipe in the tree, once each
+    pub recipes: Vec<Recipe>,
+    /// Whether the tree was cut short because it got too big
+    pub truncated: bool,
+}
+
+/// One recipe in a crafting path
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PathStep {
+    pub recipe_id: i32,
+    pub machine_id: i32,
+    pub machine_name: String,
+    /// The item that goes in to the recipe
+    pub input_id: i32,
+    pub input_name: String,
+    /// The item that comes out, and goes in to the next step
+    pub output_id: i32,
+    pub output_name: String,
+}
+
+/// A chain of recipes that turns one item in to another
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CraftingPath {
+    /// The number of steps or machines, depending on what was asked for
+    pub cost: i64,
+    pub steps: Vec<PathStep>,
+}
+
+/// The answer to a path query
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CraftingPaths {
+    /// The cheapest paths, cheapest first
+    pub paths: Vec<CraftingPath>,
+    /// Whether the search was cut short, in which case cheaper paths may be
+    /// missing
+    pub truncated: bool,
+}
+
+/// How many times a recipe has to be performed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CraftCount {
+    pub recipe_id: i32,
+    pub crafts: i64,
+}
+
+/// Some amount of an item in a bill of materials. Plans can need far more of
+/// something than any recipe does, so this has room for bigger quantities than
+/// an `ItemSpec`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BomItem {
+    pub item_id: i32,
+    pub item_name: String,
+    pub minecraft_id: String,
+    pub ty: ItemType,
+    pub quantity: i64,
+}
+
+impl BomItem {
+    /// `item`, in a different amount
+    pub fn new(item: &ItemSpec, quantity: i64) -> BomItem {
+        BomItem {
+            item_id: item.item_id,
+            item_name: item.item_name.clone(),
+            minecraft_id: item.minecraft_id.clone(),
+            ty: item.ty,
+            quantity,
+        }
+    }
+}
+
+/// A computed bill of materials
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bom {
+    /// Everything that has to be gathered by hand
+    pub raw: Vec<BomItem>,
+    /// Every recipe that has to be performed
+    pub crafts: Vec<CraftCount>,
+    /// Everything that is left over once the targets have been made
+    pub leftovers: Vec<BomItem>,
+    /// Tools and catalysts that are needed, but not used up. These are already
+    /// included in `raw` and `crafts` (once each).
+    pub tools: Vec<BomItem>,
+}
+
+/// A reference to an item, either by its internal ID # or by its Minecraft
+/// string ID
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
+pub enum ItemRef {
+    Id(i32),
+    MinecraftId(String),
+}
+
+/// A request to look up several items at once
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemLookupRequest {
+    pub items: Vec<ItemRef>,
+}
+
+/// The result of looking up a single item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ItemLookup {
+    /// The reference that was looked up
+    pub requested: ItemRef,
+    /// The item it refers to, or nothing if there is no such item
+    pub item: Option<sql::Item>,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+/// Some amount of an item, referred to by its Minecraft ID
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManualStack {
+    pub item: String,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+    /// Whether `item` names a fluid rather than an item
+    #[serde(default)]
+    pub fluid: bool,
+}
+
+/// A recipe written by hand, for things JEI doesn't know about or gets wrong
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ManualRecipe {
+    /// The JEI category UID of the machine
+    pub machine: String,
+    /// The alternatives for each input slot
+    pub inputs: Vec<Vec<ManualStack>>,
+    pub outputs: Vec<ManualStack>,
+}
@@ -0,0 +1,262 @@
+//! Reading jeiexporter folders that have been packed up.
+//!
+//! Exports are shared as a zip or a gzipped tarball of the folder the
+//! exporter wrote, which is the one holding `exports`. Archives made by
+//! zipping the folder itself rather than its contents are fine too.
+
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use tar;
+use types::MCCraftError;
+use zip::ZipArchive;
+
+/// How deep in the archive to look for the `exports` folder
+const MAX_EXPORT_DEPTH: usize = 3;
+
+enum Format {
+    Zip,
+    TarGz,
+}
+
+/// Work out what kind of archive starts with `header`
+fn detect(header: &[u8]) -> Result<Format, MCCraftError> {
+    if header.starts_with(b"PK\x03\x04") {
+        Ok(Format::Zip)
+    } else if header.starts_with(&[0x1f, 0x8b]) {
+        Ok(Format::TarGz)
+    } else {
+        Err(MCCraftError::BadExport(
+            "expected a zip or a .tar.gz archive".to_owned(),
+        ))
+    }
+}
+
+/// Unpack `bytes` into `dest`, returning the folder that holds `exports`.
+/// Archives that unpack to more than `max_size` bytes are rejected part way
+/// through, so that a small upload can't fill up the disk.
+pub fn extract(bytes: &[u8], dest: &Path, max_size: u64) -> Result<PathBuf, MCCraftError> {
+    fs::create_dir_all(dest)?;
+    match detect(bytes)? {
+        Format::Zip => extract_zip(bytes, dest, max_size)?,
+        Format::TarGz => extract_tar(bytes, dest, max_size)?,
+    }
+
+    find_export_root(dest, MAX_EXPORT_DEPTH)?.ok_or_else(|| {
+        MCCraftError::BadExport("the archive doesn't contain an exports folder".to_owned())
+    })
+}
+
+fn too_big(max_size: u64) -> MCCraftError {
+    MCCraftError::BadExport(format!(
+        "the archive unpacks to more than {} MB",
+        max_size / (1024 * 1024)
+    ))
+}
+
+fn extract_tar(bytes: &[u8], dest: &Path, max_size: u64) -> Result<(), MCCraftError> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bytes));
+    let mut written: u64 = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // This is how much the entry reads out to, sparse files included
+        written = written.saturating_add(entry.size());
+        if written > max_size {
+            return Err(too_big(max_size));
+        }
+        entry.unpack_in(dest)?;
+    }
+    Ok(())
+}
+
+fn extract_zip(bytes: &[u8], dest: &Path, max_size: u64) -> Result<(), MCCraftError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+    let mut written: u64 = 0;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        // Entries with absolute paths or `..` would end up outside of dest
+        let path = match file.enclosed_name() {
+            Some(name) => dest.join(name),
+            None => {
+                return Err(MCCraftError::BadExport(format!(
+                    "the archive contains an unsafe path: {}",
+                    file.name()
+                )))
+            }
+        };
+        if file.name().ends_with('/') {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // The sizes in the zip can't be trusted, so count what comes out
+        let remaining = max_size - written;
+        written += io::copy(
+            &mut (&mut file).take(remaining + 1),
+            &mut fs::File::create(&path)?,
+        )?;
+        if written > max_size {
+            return Err(too_big(max_size));
+        }
+    }
+    Ok(())
+}
+
+fn find_export_root(folder: &Path, depth: usize) -> Result<Option<PathBuf>, MCCraftError> {
+    if folder.join("exports").is_dir() {
+        return Ok(Some(folder.to_owned()));
+    }
+    if depth == 0 {
+        return Ok(None);
+    }
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if let Some(root) = find_export_root(&path, depth - 1)? {
+                return Ok(Some(root));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Call `f` with the name and contents of every file in the `exports` folder
+/// of the archive at `path`, without unpacking it
+pub fn read_exports(
+    path: &Path,
+    mut f: impl FnMut(&str, &mut dyn Read) -> Result<(), MCCraftError>,
+) -> Result<(), MCCraftError> {
+    let mut file = File::open(path)?;
+    let mut header = [0; 4];
+    let header_len = file.read(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut found = false;
+    match detect(&header[..header_len])? {
+        Format::Zip => {
+            let mut archive = ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                let name = match export_file_name(entry.name()) {
+                    Some(name) => name.to_owned(),
+                    None => continue,
+                };
+                found = true;
+                f(&name, &mut entry)?;
+            }
+        }
+        Format::TarGz => {
+            let mut archive = tar::Archive::new(GzDecoder::new(file));
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = match export_file_name(&entry.path()?.to_string_lossy()) {
+                    Some(name) => name.to_owned(),
+                    None => continue,
+                };
+                found = true;
+                f(&name, &mut entry)?;
+            }
+        }
+    }
+
+    if found {
+        Ok(())
+    } else {
+        Err(MCCraftError::BadExport(
+            "the archive doesn't contain an exports folder".to_owned(),
+        ))
+    }
+}
+
+/// The file name of `path` if it's a file directly inside a folder called
+/// `exports`
+fn export_file_name(path: &str) -> Option<&str> {
+    let mut parts = path.rsplit('/');
+    let name = parts.next()?;
+    if parts.next()? == "exports" && !name.is_empty() {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    /// Where the test archives keep their one recipe file, which holds
+    /// however many zeroes the test asks for
+    const RECIPES: &str = "jeiexporter/exports/minecraft.crafting.json";
+
+    fn zip_export(size: usize) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(RECIPES, FileOptions::default()).unwrap();
+        zip.write_all(&vec![0; size]).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_export(size: usize) -> Vec<u8> {
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, RECIPES, &vec![0; size][..]).unwrap();
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn extract_to_temp(name: &str, bytes: &[u8], max_size: u64) -> Result<PathBuf, MCCraftError> {
+        let dest = std::env::temp_dir().join(format!(
+            "mccraft-archive-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let result = extract(bytes, &dest, max_size);
+        fs::remove_dir_all(&dest).unwrap();
+        result.map(|x| x.strip_prefix(&dest).unwrap().to_owned())
+    }
+
+    #[test]
+    fn extracts_archives_within_the_limit() {
+        let root = extract_to_temp("zip-ok", &zip_export(1000), 1000).unwrap();
+        assert_eq!(root, Path::new("jeiexporter"));
+        let root = extract_to_temp("tar-ok", &tar_export(1000), 1000).unwrap();
+        assert_eq!(root, Path::new("jeiexporter"));
+    }
+
+    #[test]
+    fn stops_extracting_past_the_limit() {
+        match extract_to_temp("zip-big", &zip_export(1001), 1000) {
+            Err(MCCraftError::BadExport(_)) => {}
+            other => panic!("expected the zip to be rejected, got {:?}", other),
+        }
+        match extract_to_temp("tar-big", &tar_export(1001), 1000) {
+            Err(MCCraftError::BadExport(_)) => {}
+            other => panic!("expected the tarball to be rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn export_file_name_takes_files_directly_in_exports() {
+        assert_eq!(export_file_name("exports/tooltipMap.json"), Some("tooltipMap.json"));
+        assert_eq!(
+            export_file_name("jeiexporter/exports/minecraft.crafting.json"),
+            Some("minecraft.crafting.json")
+        );
+    }
+
+    #[test]
+    fn export_file_name_skips_everything_else() {
+        assert_eq!(export_file_name("tooltipMap.json"), None);
+        assert_eq!(export_file_name("exports/"), None);
+        assert_eq!(export_file_name("exports/icons/stone.png"), None);
+        assert_eq!(export_file_name("other/minecraft.crafting.json"), None);
+    }
+}
@@ -0,0 +1,59 @@
+//! Deciding which recipe inputs are used up by a recipe.
+//!
+//! JEI doesn't tell us whether an input is consumed, so we guess:
+//!
+//!  - An input that also shows up as an output with the same quantity is
+//!    returned by the recipe (catalysts, mostly).
+//!  - Inputs with names that look like tools or casts are assumed to survive
+//!    the recipe.
+//!
+//! When the guess is wrong, it can be corrected with an overrides file. Set
+//! `MCCRAFT_CONSUMPTION_OVERRIDES` to the path of a JSON list of entries like
+//!
+//! ```json
+//! { "item": "tconstruct:cast_custom:0", "machine": "Casting Table", "consumed": false }
+//! ```
+//!
+//! `machine` is the name of the JEI category, and can be left out to apply the
+//! override to every recipe that uses the item.
+
+use std::path::Path;
+use types::MCCraftError;
+
+/// Words that, at the end of an item name, suggest that the item isn't used up
+/// when crafting with it
+const TOOL_SUFFIXES: &[&str] = &[
+    "Cast", "Mold", "Mould", "Catalyst", "Hammer", "Wrench", "Saw", "Knife", "Cutter",
+];
+
+/// One entry in the overrides file
+#[derive(Deserialize, Debug)]
+pub struct ConsumptionOverride {
+    /// Minecraft ID of the item
+    pub item: String,
+    /// JEI category name the override applies to. Applies everywhere if
+    /// missing.
+    pub machine: Option<String>,
+    /// Whether the item is used up by the recipe
+    pub consumed: bool,
+}
+
+/// Load the consumption overrides from `path`. No path means no overrides.
+pub fn load(path: Option<impl AsRef<Path>>) -> Result<Vec<ConsumptionOverride>, MCCraftError> {
+    match path {
+        Some(path) => {
+            let file = std::fs::File::open(path.as_ref())?;
+            Ok(serde_json::from_reader(file)?)
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Guess whether an item with the given human-readable name is a tool
+pub fn looks_like_tool(human_name: &str) -> bool {
+    human_name
+        .split_whitespace()
+        .last()
+        .map(|x| TOOL_SUFFIXES.contains(&x))
+        .unwrap_or(false)
+}
@@ -0,0 +1,43 @@
+//! Configuration for which items are filled fluid containers.
+//!
+//! The table is a JSON list of entries like
+//!
+//! ```json
+//! { "filled": "minecraft:water_bucket:0", "fluid": "water", "amount": 1000, "empty": "minecraft:bucket:0" }
+//! ```
+//!
+//! `empty` can be left out for containers that are used up when emptied (most
+//! mods' cells and capsules work like this). By default, only the vanilla
+//! buckets are known. Set `MCCRAFT_FLUID_CONTAINERS` to the path of a file in
+//! the above format to replace them.
+
+use std::path::Path;
+use types::MCCraftError;
+
+/// The containers that we know about without any configuration
+const DEFAULT_CONTAINERS: &str = include_str!("../fluid_containers.json");
+
+/// One entry in the container table
+#[derive(Deserialize, Debug)]
+pub struct FluidContainer {
+    /// Minecraft ID of the filled container
+    pub filled: String,
+    /// Name of the fluid that it holds
+    pub fluid: String,
+    /// How much fluid it holds, in mB
+    pub amount: i32,
+    /// Minecraft ID of the container after it has been emptied
+    pub empty: Option<String>,
+}
+
+/// Load the container table from `path`, or the default table if no path was
+/// given.
+pub fn load(path: Option<impl AsRef<Path>>) -> Result<Vec<FluidContainer>, MCCraftError> {
+    match path {
+        Some(path) => {
+            let file = std::fs::File::open(path.as_ref())?;
+            Ok(serde_json::from_reader(file)?)
+        }
+        None => Ok(serde_json::from_str(DEFAULT_CONTAINERS)?),
+    }
+}
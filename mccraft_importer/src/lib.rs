@@ -0,0 +1,362 @@
+//! Reads the folder written by jeiexporter and loads the recipes in it into
+//! the database.
+//!
+//! The `mccraft_importer` binary is a command line front end for this, and the
+//! web server uses it to import exports uploaded through the admin API.
+
+extern crate diesel;
+extern crate flate2;
+extern crate fxhash;
+#[macro_use]
+extern crate log;
+extern crate mccraft_core;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate string_interner;
+extern crate tar;
+extern crate zip;
+
+pub mod archive;
+pub mod consumption;
+pub mod containers;
+pub mod overrides;
+pub mod progress;
+pub mod raw_items;
+pub mod recipe_db;
+pub mod report;
+pub mod source;
+pub mod stats;
+pub mod types;
+
+use diesel::prelude::*;
+use diesel::PgConnection;
+use serde::de::DeserializeSeed;
+use mccraft_core::json::recipe;
+use std::collections::HashMap;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use string_interner::Sym;
+
+pub use progress::{LogProgress, Progress, Stage};
+pub use recipe_db::{RecipeDatabase, WriteOptions};
+pub use source::ExportSource;
+use report::ImportReport;
+use types::*;
+
+// Convert a list of fluids into a slot that accepts any of those fluids
+fn slot_from_fluids(db: &mut RecipeDatabase, ingredient: &recipe::IngredientFluid) -> CraftingSlot {
+    let mut slot = CraftingSlot::new();
+    slot.position = Some((ingredient.x as i32, ingredient.y as i32));
+    slot.allowed_elements.reserve(ingredient.fluids.len());
+    for ref fluid in &ingredient.fluids {
+        slot.allowed_elements
+            .push(RecipeComponent::from_fluid(db, fluid));
+    }
+
+    slot
+}
+
+fn slot_from_items(db: &mut RecipeDatabase, ingredient: &recipe::IngredientItem) -> CraftingSlot {
+    let mut slot = CraftingSlot::new();
+    slot.position = Some((ingredient.x as i32, ingredient.y as i32));
+    slot.allowed_elements.reserve(ingredient.stacks.len());
+    for ref stack in &ingredient.stacks {
+        slot.allowed_elements
+            .push(RecipeComponent::from_item(db, stack));
+    }
+
+    slot
+}
+
+fn handle_covariant_recipe(
+    db: &mut RecipeDatabase,
+    machine: Sym,
+    jrecipe: &recipe::Recipe,
+    covariant_count: usize,
+    out: &mut Vec<Recipe>,
+) {
+    let mut template = Recipe::new(machine);
+    let mut covariant_inputs = Vec::new();
+    let mut covariant_outputs = Vec::new();
+
+    // Push all the item slots that aren't covariant in to the template
+    for item_slot in &jrecipe.ingredient_items {
+        if item_slot.stacks.len() == 0 {
+            // We don't care about slots with nothing in them
+            continue;
+        }
+
+        if item_slot.stacks.len() == covariant_count {
+            // This is one of the stacks that is covariant. Stuff a reference to it into the right list
+            if item_slot.is_input {
+                covariant_inputs.push(item_slot);
+            } else {
+                covariant_outputs.push(item_slot);
+            }
+            continue;
+        }
+
+        // Otherwise, add it to the template
+        if item_slot.is_input {
+            template.inputs.push(slot_from_items(db, &item_slot));
+        } else {
+            // We can only deal with one kind of covariance at a time
+            assert!(item_slot.stacks.len() == 1);
+            template
+                .outputs
+                .push(RecipeComponent::from_item(db, &item_slot.stacks[0]));
+        }
+    }
+
+    // Blindly push all of the fluids onto the template
+    for ref fluid_slot in &jrecipe.ingredient_fluids {
+        if fluid_slot.fluids.len() == 0 {
+            continue;
+        }
+
+        if fluid_slot.is_input {
+            let slot = slot_from_fluids(db, &fluid_slot);
+            template.inputs.push(slot);
+        } else {
+            // we don't handle covariance for fluids, so there better only be one output
+            assert!(fluid_slot.fluids.len() == 1);
+            template
+                .outputs
+                .push(RecipeComponent::from_fluid(db, &fluid_slot.fluids[0]))
+        }
+    }
+
+    // Using the template, push out recipe variants for all the covariants.
+    for i in 0..covariant_count {
+        let mut recipe = template.clone();
+
+        for ref input in &covariant_inputs {
+            let mut slot = CraftingSlot::new();
+            slot.position = Some((input.x as i32, input.y as i32));
+            slot.allowed_elements
+                .push(RecipeComponent::from_item(db, &input.stacks[i]));
+            recipe.inputs.push(slot);
+        }
+
+        for ref output in &covariant_outputs {
+            recipe
+                .outputs
+                .push(RecipeComponent::from_item(db, &output.stacks[i]));
+        }
+
+        out.push(recipe);
+    }
+}
+
+/// Convert a recipe from the export, pushing the result (or results, for
+/// covariant recipes) onto `out`
+fn convert_recipe(
+    db: &mut RecipeDatabase,
+    machine: Sym,
+    jrecipe: &recipe::Recipe,
+    out: &mut Vec<Recipe>,
+) {
+    let mut recipe = Recipe::new(machine);
+    let mut discard_recipe = false;
+    for item_slot in &jrecipe.ingredient_items {
+        if item_slot.stacks.len() == 0 {
+            continue;
+        }
+
+        let slot = slot_from_items(db, &item_slot);
+        if item_slot.is_input {
+            recipe.inputs.push(slot);
+        } else {
+            if item_slot.stacks.len() != 1 {
+                handle_covariant_recipe(db, machine, jrecipe, item_slot.stacks.len(), out);
+                discard_recipe = true;
+            }
+            recipe
+                .outputs
+                .push(RecipeComponent::from_item(db, &item_slot.stacks[0]));
+        }
+    }
+    for fluid_slot in &jrecipe.ingredient_fluids {
+        if fluid_slot.fluids.len() == 0 {
+            continue;
+        }
+
+        if fluid_slot.is_input {
+            let slot = slot_from_fluids(db, &fluid_slot);
+            recipe.inputs.push(slot);
+        } else {
+            assert!(fluid_slot.fluids.len() == 1);
+            recipe
+                .outputs
+                .push(RecipeComponent::from_fluid(db, &fluid_slot.fluids[0]));
+        }
+    }
+    if !discard_recipe {
+        out.push(recipe);
+    }
+}
+
+/// The primary recipe import procedure
+fn import_recipes(
+    db: &mut RecipeDatabase,
+    file_name: &str,
+    contents: &mut dyn Read,
+) -> Result<(), MCCraftError> {
+    // Recipes are converted as they're parsed, so only one of them is ever
+    // held in its JSON form, but the converted recipes are collected for the
+    // whole file: the exporter can write the category after the recipes, so
+    // they only get their machine once the whole file is read.
+    let placeholder = db.get_or_intern("");
+    let mut recipes = Vec::new();
+    let info = {
+        let mut deser = serde_json::Deserializer::from_reader(BufReader::new(contents));
+        let info = recipe::StreamingInstance {
+            on_recipe: |jrecipe| convert_recipe(db, placeholder, &jrecipe, &mut recipes),
+        }.deserialize(&mut deser)?;
+        deser.end()?;
+        info
+    };
+
+    // The exporter names each file after the category it holds, so fall back
+    // to that if the category UID isn't in the file itself.
+    let uid = match info.uid {
+        Some(uid) => uid,
+        None => Path::new(file_name)
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_else(|| info.bg.tex.clone()),
+    };
+    let machine = db.get_or_intern(uid);
+    db.add_machine(machine, info.category, info.bg.tex);
+    for catalyst in &info.catalysts {
+        let catalyst = RecipeComponent::from_item(db, catalyst);
+        db.add_machine_catalyst(machine, catalyst.get_name());
+    }
+
+    for mut recipe in recipes {
+        recipe.machine = machine;
+        db.add_recipe(recipe);
+    }
+
+    Ok(())
+}
+
+/// Parses the tooltips, returning a map from MC ID to human-readable name.
+fn import_tooltips(db: &mut RecipeDatabase, contents: &mut dyn Read) -> Result<(), MCCraftError> {
+    let tooltips: HashMap<String, String> = serde_json::from_reader(BufReader::new(contents))?;
+
+    for (mc_name, human_name) in tooltips.into_iter() {
+        let mc_name = db.get_or_intern(mc_name);
+        db.associate_name(mc_name, human_name);
+    }
+
+    Ok(())
+}
+
+fn read_exports(
+    source: &ExportSource,
+    db: &mut RecipeDatabase,
+    progress: &mut dyn Progress,
+) -> Result<(), MCCraftError> {
+    source.for_each_file(|file_name, contents| {
+        if !source::is_json(file_name) {
+            progress.warn(format!("Skipping {} since it isn't a JSON file", file_name));
+        } else if file_name == "tooltipMap.json" {
+            import_tooltips(db, contents)?;
+        } else if file_name == "lookupMap.json" {
+            info!("Skipping lookup map since it's just an inverse tooltip map");
+        } else {
+            let start_len = db.num_recipes();
+            match import_recipes(db, file_name, contents) {
+                Ok(()) => {
+                    progress.log(format!(
+                        "Processed {}: {} recipes added",
+                        file_name,
+                        db.num_recipes() - start_len
+                    ));
+                    db.record_file(None);
+                }
+                Err(e) => {
+                    progress.warn(format!("Processing {} failed: {:?}", file_name, e));
+                    db.record_file(Some(format!("{}: {:?}", file_name, e)));
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    progress.log(format!(
+        "Ingested {} recipes ({} duplicate copies merged)",
+        db.num_recipes(),
+        db.num_duplicates()
+    ));
+
+    Ok(())
+}
+
+/// Hand-written corrections to apply to an export as it's read
+#[derive(Debug, Default)]
+pub struct IngestOptions {
+    pub recipe_overrides: Vec<overrides::RecipeOverride>,
+    pub consumption_overrides: Vec<consumption::ConsumptionOverride>,
+}
+
+/// Read the JEI export at `path`, and apply the recipe and consumption
+/// overrides to what we found. `path` is the folder holding `exports`, or an
+/// archive of it.
+pub fn ingest(path: &Path, options: &IngestOptions) -> Result<RecipeDatabase, MCCraftError> {
+    ingest_with_progress(
+        &ExportSource::from_paths(vec![path.to_owned()]),
+        options,
+        &mut LogProgress,
+    )
+}
+
+pub fn ingest_with_progress(
+    source: &ExportSource,
+    options: &IngestOptions,
+    progress: &mut dyn Progress,
+) -> Result<RecipeDatabase, MCCraftError> {
+    progress.stage(Stage::Reading);
+    let mut recipe_db = RecipeDatabase::new();
+    read_exports(source, &mut recipe_db, progress)?;
+
+    recipe_db.apply_overrides(&options.recipe_overrides);
+    recipe_db.mark_unconsumed_inputs(&options.consumption_overrides);
+
+    progress.stage(Stage::GroupingIngredients);
+    recipe_db.detect_ingredient_groups();
+
+    Ok(recipe_db)
+}
+
+/// Import a JEI export into the database
+pub fn import(
+    source: &ExportSource,
+    conn: &PgConnection,
+    ingest_options: &IngestOptions,
+    write_options: &WriteOptions,
+    progress: &mut dyn Progress,
+) -> Result<ImportReport, MCCraftError> {
+    let recipe_db = ingest_with_progress(source, ingest_options, progress)?;
+    recipe_db.write_with_progress(conn, write_options, progress)?;
+    progress.stage(Stage::Done);
+
+    Ok(recipe_db.report())
+}
+
+/// Delete every recipe and machine, along with the ingredient groups and fluid
+/// containers. Items are kept, since saved plans refer to them.
+pub fn purge(conn: &PgConnection) -> QueryResult<()> {
+    conn.transaction(|| {
+        diesel::sql_query(
+            "TRUNCATE mccraft.crafting_components, mccraft.input_slots, mccraft.outputs, \
+             mccraft.recipes, mccraft.machine_catalysts, mccraft.machines, \
+             mccraft.ingredient_group_members, mccraft.ingredient_groups, \
+             mccraft.fluid_containers",
+        ).execute(conn)
+        .map(|_| ())
+    })
+}
@@ -1,229 +1,255 @@
-extern crate fxhash;
-extern crate mccraft_core;
-extern crate serde;
-extern crate serde_json;
-#[macro_use]
-extern crate log;
+extern crate clap;
 extern crate diesel;
 extern crate dotenv;
 extern crate env_logger;
-extern crate string_interner;
-
-mod recipe_db;
-mod types;
+#[macro_use]
+extern crate log;
+extern crate mccraft_core;
+extern crate mccraft_importer;
+extern crate serde;
+extern crate serde_json;
 
 use diesel::{Connection, PgConnection};
-use mccraft_core::json::recipe;
-use std::collections::HashMap;
+use mccraft_core::analysis::HealthReport;
+use mccraft_core::conflicts::ConflictReport;
+use mccraft_core::diff::{PackDiff, Snapshot};
+use mccraft_importer::stats::PackStats;
+use mccraft_importer::types::MCCraftError;
+use mccraft_importer::{
+    consumption, containers, overrides, raw_items, ExportSource, IngestOptions, LogProgress,
+    WriteOptions,
+};
 use std::ffi::OsStr;
-use string_interner::Sym;
-
-use recipe_db::RecipeDatabase;
-use types::*;
-
-// Convert a list of fluids into a slot that accepts any of those fluids
-fn slot_from_fluids(db: &mut RecipeDatabase, ingredient: &recipe::IngredientFluid) -> CraftingSlot {
-    let mut slot = CraftingSlot::new();
-    slot.allowed_elements.reserve(ingredient.fluids.len());
-    for ref fluid in &ingredient.fluids {
-        slot.allowed_elements
-            .push(RecipeComponent::from_fluid(db, fluid));
-    }
+use std::path::{Path, PathBuf};
 
-    slot
+fn connect() -> PgConnection {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    info!("Connecting to database {}", database_url);
+
+    PgConnection::establish(&database_url).expect(&format!("error connecting to {}", database_url))
 }
 
-fn slot_from_items(db: &mut RecipeDatabase, ingredient: &recipe::IngredientItem) -> CraftingSlot {
-    let mut slot = CraftingSlot::new();
-    slot.allowed_elements.reserve(ingredient.stacks.len());
-    for ref stack in &ingredient.stacks {
-        slot.allowed_elements
-            .push(RecipeComponent::from_item(db, stack));
+fn print_report<T: serde::Serialize>(report: &T, text: String, json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(report).expect("Failed to serialize the report")
+        );
+    } else {
+        print!("{}", text);
     }
-
-    slot
 }
 
-fn handle_covariant_recipe(
-    db: &mut RecipeDatabase,
-    machine: Sym,
-    jrecipe: &recipe::Recipe,
-    covariant_count: usize,
-) {
-    let mut template = Recipe::new(machine);
-    let mut covariant_inputs = Vec::new();
-    let mut covariant_outputs = Vec::new();
-
-    // Push all the item slots that aren't covariant in to the template
-    for item_slot in &jrecipe.ingredient_items {
-        if item_slot.stacks.len() == 0 {
-            // We don't care about slots with nothing in them
-            continue;
-        }
+/// Load the overrides named by `MCCRAFT_RECIPE_OVERRIDES` and
+/// `MCCRAFT_CONSUMPTION_OVERRIDES`
+fn ingest_options() -> Result<IngestOptions, MCCraftError> {
+    Ok(IngestOptions {
+        recipe_overrides: overrides::load(std::env::var_os("MCCRAFT_RECIPE_OVERRIDES"))?,
+        consumption_overrides: consumption::load(std::env::var_os(
+            "MCCRAFT_CONSUMPTION_OVERRIDES",
+        ))?,
+    })
+}
 
-        if item_slot.stacks.len() == covariant_count {
-            // This is one of the stacks that is covariant. Stuff a reference to it into the right list
-            if item_slot.is_input {
-                covariant_inputs.push(item_slot);
-            } else {
-                covariant_outputs.push(item_slot);
-            }
-            continue;
-        }
+/// Load the files named by `MCCRAFT_FLUID_CONTAINERS` and `MCCRAFT_RAW_ITEMS`
+fn write_options(purge_first: bool) -> Result<WriteOptions, MCCraftError> {
+    Ok(WriteOptions {
+        fluid_containers: containers::load(std::env::var_os("MCCRAFT_FLUID_CONTAINERS"))?,
+        raw_items: raw_items::load(std::env::var_os("MCCRAFT_RAW_ITEMS"))?,
+        purge_first,
+        // Nobody else should be using the database during a command line import
+        drop_constraints: true,
+    })
+}
 
-        // Otherwise, add it to the template
-        if item_slot.is_input {
-            template.inputs.push(slot_from_items(db, &item_slot));
-        } else {
-            // We can only deal with one kind of covariance at a time
-            assert!(item_slot.stacks.len() == 1);
-            template
-                .outputs
-                .push(RecipeComponent::from_item(db, &item_slot.stacks[0]));
+/// Import a JEI export into the database
+fn import(source: &ExportSource, report_path: Option<&OsStr>, replace: bool) {
+    let ingest_options = ingest_options().expect("Failed to load the importer configuration");
+    let write_options = write_options(replace).expect("Failed to load the importer configuration");
+    let recipe_db = mccraft_importer::ingest_with_progress(source, &ingest_options, &mut LogProgress)
+        .expect("Failed to read the export");
+    recipe_db
+        .write(&connect(), &write_options)
+        .expect("Failed to write the recipes to the database");
+
+    let report = recipe_db.report();
+    match report_path {
+        Some(path) => {
+            let file = std::fs::File::create(path).expect("Failed to create the import report");
+            serde_json::to_writer_pretty(file, &report).expect("Failed to write the import report");
+            println!("Wrote the import report to {:?}", path);
         }
+        None => print!("{}", report.to_text()),
     }
+}
 
-    // Blindly push all of the fluids onto the template
-    for ref fluid_slot in &jrecipe.ingredient_fluids {
-        if fluid_slot.fluids.len() == 0 {
-            continue;
+/// Read a JEI export and the importer configuration without touching the
+/// database. Exits with an error if any of it couldn't be read.
+fn validate(source: &ExportSource, json: bool) {
+    let ingest_options = match ingest_options().and_then(|x| write_options(false).map(|_| x)) {
+        Ok(options) => options,
+        Err(e) => {
+            error!("Failed to load the importer configuration: {:?}", e);
+            std::process::exit(1);
         }
-
-        if fluid_slot.is_input {
-            let slot = slot_from_fluids(db, &fluid_slot);
-            template.inputs.push(slot);
-        } else {
-            // we don't handle covariance for fluids, so there better only be one output
-            assert!(fluid_slot.fluids.len() == 1);
-            template
-                .outputs
-                .push(RecipeComponent::from_fluid(db, &fluid_slot.fluids[0]))
+    };
+    let recipe_db = match mccraft_importer::ingest_with_progress(source, &ingest_options, &mut LogProgress) {
+        Ok(recipe_db) => recipe_db,
+        Err(e) => {
+            error!("Failed to read the export: {:?}", e);
+            std::process::exit(1);
         }
-    }
-
-    // Using the template, push out recipe variants for all the covariants.
-    for i in 0..covariant_count {
-        let mut recipe = template.clone();
+    };
 
-        for ref input in &covariant_inputs {
-            let mut slot = CraftingSlot::new();
-            slot.allowed_elements
-                .push(RecipeComponent::from_item(db, &input.stacks[i]));
-            recipe.inputs.push(slot);
-        }
+    let report = recipe_db.report();
+    print_report(&report, report.to_text(), json);
+    if !report.failed_files.is_empty() {
+        std::process::exit(1);
+    }
+}
 
-        for ref output in &covariant_outputs {
-            recipe
-                .outputs
-                .push(RecipeComponent::from_item(db, &output.stacks[i]));
-        }
+/// Count what's in a pack
+fn stats(source: &str, json: bool) {
+    let stats = PackStats::compute(&load_snapshot(source));
+    print_report(&stats, stats.to_text(), json);
+}
 
-        db.add_recipe(recipe);
+/// Delete the imported recipes from the database
+fn purge(confirmed: bool) {
+    if !confirmed {
+        error!("This deletes every recipe and machine in the database. Pass --yes to go ahead.");
+        std::process::exit(1);
     }
+    mccraft_importer::purge(&connect()).expect("Failed to purge the database");
+    println!("Deleted every recipe and machine");
 }
 
-/// The primary recipe import procedure
-fn import_recipes(
-    db: &mut RecipeDatabase,
-    json_path: impl AsRef<std::path::Path>,
-) -> Result<(), MCCraftError> {
-    let instance_file = std::fs::File::open(json_path.as_ref())?;
-    let instance: recipe::CraftingInstance = serde_json::from_reader(instance_file)?;
-    let machine = db.get_or_intern(instance.bg.tex);
-    db.add_machine(machine, instance.category);
-
-    let mut discard_recipe = false;
-    for jrecipe in instance.recipes {
-        let mut recipe = Recipe::new(machine);
-        for item_slot in &jrecipe.ingredient_items {
-            if item_slot.stacks.len() == 0 {
-                continue;
-            }
-
-            let slot = slot_from_items(db, &item_slot);
-            if item_slot.is_input {
-                recipe.inputs.push(slot);
-            } else {
-                if item_slot.stacks.len() != 1 {
-                    handle_covariant_recipe(db, machine, &jrecipe, item_slot.stacks.len());
-                    discard_recipe = true;
-                }
-                recipe
-                    .outputs
-                    .push(RecipeComponent::from_item(db, &item_slot.stacks[0]));
-            }
-        }
-        for fluid_slot in jrecipe.ingredient_fluids {
-            if fluid_slot.fluids.len() == 0 {
-                continue;
-            }
-
-            if fluid_slot.is_input {
-                let slot = slot_from_fluids(db, &fluid_slot);
-                recipe.inputs.push(slot);
-            } else {
-                assert!(fluid_slot.fluids.len() == 1);
-                recipe
-                    .outputs
-                    .push(RecipeComponent::from_fluid(db, &fluid_slot.fluids[0]));
-            }
-        }
-        if !discard_recipe {
-            db.add_recipe(recipe);
-        }
-        discard_recipe = false;
-    }
+/// Print a report of everything that looks wrong with the imported recipes
+fn analyze(json: bool) {
+    let report = HealthReport::compute(&connect()).expect("Failed to analyze the recipe graph");
+    print_report(&report, report.to_text(), json);
+}
 
-    Ok(())
+/// Print every pair of recipes that take the same inputs, grouped by machine
+fn conflicts(shaped_categories: &[String], json: bool) {
+    let report = ConflictReport::compute(&connect(), shaped_categories)
+        .expect("Failed to look for conflicts");
+    print_report(&report, report.to_text(), json);
 }
 
-/// Parses the tooltips, returning a map from MC ID to human-readable name.
-fn import_tooltips(
-    db: &mut RecipeDatabase,
-    json_path: impl AsRef<std::path::Path>,
-) -> Result<(), MCCraftError> {
-    let instance_file = std::fs::File::open(json_path.as_ref())?;
-    let tooltips: HashMap<String, String> = serde_json::from_reader(instance_file)?;
-
-    for (mc_name, human_name) in tooltips.into_iter() {
-        let mc_name = db.get_or_intern(mc_name);
-        db.associate_name(mc_name, human_name);
+/// Load a pack, which is either a database URL or a JEI export
+fn load_snapshot(source: &str) -> Snapshot {
+    if source.starts_with("postgres://") || source.starts_with("postgresql://") {
+        info!("Connecting to database {}", source);
+        let conn = PgConnection::establish(source)
+            .unwrap_or_else(|e| panic!("error connecting to {}: {}", source, e));
+        return Snapshot::load(&conn).expect("Failed to load the recipes from the database");
     }
 
-    Ok(())
+    let options = ingest_options().expect("Failed to load the importer configuration");
+    mccraft_importer::ingest(Path::new(source), &options)
+        .expect("Failed to read the export")
+        .snapshot()
 }
 
-fn ingest(path: &std::path::PathBuf) -> RecipeDatabase {
-    let mut db = RecipeDatabase::new();
+/// Print a changelog between two pack versions
+fn diff(old: &str, new: &str, json: bool) {
+    let old = load_snapshot(old);
+    let new = load_snapshot(new);
+    let changes = PackDiff::compute(&old, &new);
+    print_report(&changes, changes.to_markdown(&old, &new), json);
+}
 
-    for file in path.read_dir().expect("iterator over exports folder") {
-        info!("Processing file {:?}", file);
-        if !file.is_ok() {
-            warn!("skipping file {:?}", file);
-            continue;
-        }
-        let file = file.unwrap();
-        let file = file.path();
-        let file_name = file.file_name();
-        if file_name == Some(OsStr::new("tooltipMap.json")) {
-            import_tooltips(&mut db, &file).expect("Failed to import tooltip map");
-        } else if file_name == Some(OsStr::new("lookupMap.json")) {
-            info!("Skipping lookup map since it's just an inverse tooltip map");
-        } else {
-            let start_len = db.num_recipes();
-            match import_recipes(&mut db, &file) {
-                Ok(()) => info!(
-                    "Processing completed successfully. {} recipes added",
-                    db.num_recipes() - start_len
+fn app_args() -> clap::ArgMatches<'static> {
+    use clap::{App, AppSettings, Arg, SubCommand};
+
+    let json = || {
+        Arg::with_name("json")
+            .long("json")
+            .help("Print JSON instead of text")
+    };
+    let export = || {
+        Arg::with_name("export")
+            .required(true)
+            .multiple(true)
+            .help(
+                "The jeiexporter folder (which holds `exports`), a .zip or .tar.gz of it, \
+                 or individual JSON files from `exports`",
+            )
+    };
+    let source = |name| {
+        Arg::with_name(name)
+            .required(true)
+            .help("A jeiexporter folder or archive, or a postgres:// database URL")
+    };
+
+    App::new("mccraft_importer")
+        .author("Reed Koser")
+        .about("Loads JEI exports into the mccraft database")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import a JEI export into $DATABASE_URL")
+                .arg(export())
+                .arg(
+                    Arg::with_name("report")
+                        .long("report")
+                        .takes_value(true)
+                        .help("Write the import report here as JSON. Defaults to $MCCRAFT_IMPORT_REPORT"),
+                ).arg(
+                    Arg::with_name("replace")
+                        .long("replace")
+                        .help("Delete the recipes already in the database first, instead of updating them"),
                 ),
-                Err(e) => warn!("Processing failed {:?}", e),
-            }
-        }
-    }
-
-    println!("Ingested {:?} recipes", db.num_recipes());
+        ).subcommand(
+            SubCommand::with_name("validate")
+                .about("Check that a JEI export and the importer configuration can be read")
+                .arg(export())
+                .arg(json()),
+        ).subcommand(
+            SubCommand::with_name("stats")
+                .about("Count the items, machines and recipes in a pack")
+                .arg(source("source"))
+                .arg(json()),
+        ).subcommand(
+            SubCommand::with_name("purge")
+                .about("Delete every recipe and machine from $DATABASE_URL")
+                .arg(
+                    Arg::with_name("yes")
+                        .long("yes")
+                        .help("Really delete them"),
+                ),
+        ).subcommand(
+            SubCommand::with_name("diff")
+                .about("Print a changelog between two versions of a pack")
+                .arg(source("old"))
+                .arg(source("new"))
+                .arg(json()),
+        ).subcommand(
+            SubCommand::with_name("analyze")
+                .about("Report problems with the recipe graph in $DATABASE_URL")
+                .arg(json()),
+        ).subcommand(
+            SubCommand::with_name("conflicts")
+                .about("List recipes in $DATABASE_URL that take the same inputs")
+                .arg(
+                    Arg::with_name("shaped")
+                        .long("shaped")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A JEI category whose recipes are compared slot by slot"),
+                ).arg(json()),
+        ).get_matches()
+}
 
-    db
+fn export_source(args: &clap::ArgMatches) -> ExportSource {
+    ExportSource::from_paths(
+        args.values_of_os("export")
+            .unwrap()
+            .map(PathBuf::from)
+            .collect(),
+    )
 }
 
 fn main() {
@@ -231,23 +257,35 @@ fn main() {
     let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info");
     env_logger::Builder::from_env(env).init();
 
-    let arguments: Vec<String> = std::env::args().collect();
-    if arguments.len() != 2 {
-        error!("Need path to jeiexporter folder");
-        return;
+    let matches = app_args();
+    match matches.subcommand() {
+        ("import", Some(args)) => {
+            let report_path = args
+                .value_of_os("report")
+                .map(|x| x.to_owned())
+                .or_else(|| std::env::var_os("MCCRAFT_IMPORT_REPORT"));
+            import(
+                &export_source(args),
+                report_path.as_deref(),
+                args.is_present("replace"),
+            )
+        }
+        ("validate", Some(args)) => validate(&export_source(args), args.is_present("json")),
+        ("stats", Some(args)) => stats(args.value_of("source").unwrap(), args.is_present("json")),
+        ("purge", Some(args)) => purge(args.is_present("yes")),
+        ("diff", Some(args)) => diff(
+            args.value_of("old").unwrap(),
+            args.value_of("new").unwrap(),
+            args.is_present("json"),
+        ),
+        ("analyze", Some(args)) => analyze(args.is_present("json")),
+        ("conflicts", Some(args)) => conflicts(
+            &args
+                .values_of("shaped")
+                .map(|x| x.map(|x| x.to_owned()).collect::<Vec<_>>())
+                .unwrap_or_default(),
+            args.is_present("json"),
+        ),
+        _ => unreachable!(),
     }
-
-    let base_folder = std::path::PathBuf::from(arguments[1].to_owned());
-    let exports_folder = base_folder.join("exports");
-
-    let recipe_db = ingest(&exports_folder);
-
-    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    info!("Connecting to database {}", database_url);
-
-    let conn = PgConnection::establish(&database_url)
-        .expect(&format!("error connecting to {}", database_url));
-
-    recipe_db.insert_items(&conn);
-    recipe_db.insert_recipes(&conn);
 }
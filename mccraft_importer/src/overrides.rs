@@ -0,0 +1,47 @@
+//! Hand-written changes to the recipes in an export.
+//!
+//! Some mods don't register their recipes with JEI, and some of the recipes
+//! JEI shows are wrong. Set `MCCRAFT_RECIPE_OVERRIDES` to the path of a JSON
+//! list of entries like
+//!
+//! ```json
+//! { "action": "add", "machine": "minecraft.crafting",
+//!   "inputs": [[{ "item": "minecraft:iron_ingot:0", "quantity": 3 }]],
+//!   "outputs": [{ "item": "minecraft:bucket:0" }] }
+//! { "action": "remove", "output": "minecraft:bucket:0", "machine": "minecraft.crafting" }
+//! ```
+//!
+//! `replace` takes a recipe like `add`, and removes every recipe on the same
+//! machine that makes its first output before adding it. `remove` applies to
+//! every machine if `machine` is left out. The overrides are applied in order.
+//! Removed recipes that an earlier import already wrote to the database are
+//! hidden there, the same way the admin interface hides recipes.
+
+use mccraft_core::web::ManualRecipe;
+use std::path::Path;
+use types::MCCraftError;
+
+/// One entry in the overrides file
+#[derive(Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum RecipeOverride {
+    Add(ManualRecipe),
+    Remove {
+        /// Minecraft ID of something the recipe makes
+        output: String,
+        /// JEI category UID of the machine. Applies everywhere if missing.
+        machine: Option<String>,
+    },
+    Replace(ManualRecipe),
+}
+
+/// Load the recipe overrides from `path`. No path means no overrides.
+pub fn load(path: Option<impl AsRef<Path>>) -> Result<Vec<RecipeOverride>, MCCraftError> {
+    match path {
+        Some(path) => {
+            let file = std::fs::File::open(path.as_ref())?;
+            Ok(serde_json::from_reader(file)?)
+        }
+        None => Ok(Vec::new()),
+    }
+}
@@ -0,0 +1,42 @@
+//! Reporting how an import is going.
+
+/// The steps of an import, in the order they happen
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Extracting,
+    Reading,
+    GroupingIngredients,
+    InsertingItems,
+    InsertingFluidContainers,
+    InsertingRecipes,
+    Done,
+}
+
+/// Somewhere to send word of how an import is going
+pub trait Progress {
+    fn stage(&mut self, stage: Stage);
+    fn log(&mut self, message: String);
+
+    /// Something went wrong, but the import can carry on
+    fn warn(&mut self, message: String) {
+        self.log(message)
+    }
+}
+
+/// Sends progress to the log
+pub struct LogProgress;
+
+impl Progress for LogProgress {
+    fn stage(&mut self, stage: Stage) {
+        info!("Import stage: {:?}", stage);
+    }
+
+    fn log(&mut self, message: String) {
+        info!("{}", message);
+    }
+
+    fn warn(&mut self, message: String) {
+        warn!("{}", message);
+    }
+}
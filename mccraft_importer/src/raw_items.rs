@@ -0,0 +1,26 @@
+//! Configuration for which items are gathered rather than crafted.
+//!
+//! The list is a JSON array of item IDs. An entry with metadata
+//! (`minecraft:log:1`) matches exactly that item, and an entry without
+//! (`minecraft:log`) matches every variant of it. Fluids are listed by name.
+//! By default, only the obvious vanilla materials are listed. Set
+//! `MCCRAFT_RAW_ITEMS` to the path of a file in the same format to replace
+//! them.
+
+use std::path::Path;
+use types::MCCraftError;
+
+/// The raw items that we know about without any configuration
+const DEFAULT_RAW_ITEMS: &str = include_str!("../raw_items.json");
+
+/// Load the raw item list from `path`, or the default list if no path was
+/// given.
+pub fn load(path: Option<impl AsRef<Path>>) -> Result<Vec<String>, MCCraftError> {
+    match path {
+        Some(path) => {
+            let file = std::fs::File::open(path.as_ref())?;
+            Ok(serde_json::from_reader(file)?)
+        }
+        None => Ok(serde_json::from_str(DEFAULT_RAW_ITEMS)?),
+    }
+}
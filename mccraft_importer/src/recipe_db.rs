@@ -1,50 +1,224 @@
 use diesel;
+use diesel::pg::upsert::excluded;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::result::{DatabaseErrorKind, Error::DatabaseError, QueryResult};
 use fxhash::{FxHashMap, FxHashSet};
+use mccraft_core::diff::{Snapshot, SnapshotRecipe, Stack};
+use mccraft_core::minecraft_id::MinecraftId;
+use mccraft_core::recipe_hash::{self, HashedStack};
 use mccraft_core::schema::mccraft as schema;
 use mccraft_core::sql::{self, Item, ItemType};
+use mccraft_core::web::{ManualRecipe, ManualStack};
 use string_interner::Sym;
-use types::{Recipe, StringInterner};
+use consumption::{self, ConsumptionOverride};
+use containers::FluidContainer;
+use overrides::RecipeOverride;
+use progress::{LogProgress, Progress, Stage};
+use report::{DuplicateRecipe, ImportReport};
+use std::collections::BTreeMap;
+use types::{
+    CraftingSlot, IngredientGroup, MCCraftError, MachineKey, NormalizedRecipe, Recipe,
+    RecipeComponent, StringInterner,
+};
+
+/// Slots with fewer alternatives than this are never turned into groups
+const MIN_GROUP_SIZE: usize = 2;
+/// A set of alternatives needs to show up in at least this many slots to
+/// become a group
+const MIN_GROUP_USES: usize = 2;
 
 /// Database of all recipes
 pub struct RecipeDatabase {
     recipes: Vec<Recipe>,
+    /// Index in to `recipes` of each distinct recipe
+    recipe_index: FxHashMap<NormalizedRecipe, usize>,
+    /// Categories of the copies we dropped, by the index of the recipe we kept
+    duplicates: BTreeMap<usize, Vec<Sym>>,
+    /// Content hashes of the recipes that overrides removed, so that copies
+    /// left in the database by earlier imports can be hidden
+    removed: Vec<String>,
     /// Interner for all the strings
     interner: StringInterner,
     /// Map from the MinecraftID for an item to its type
     types_map: FxHashMap<Sym, ItemType>,
     /// Map from the MinecraftID for an item to its human-readable name
     names_map: FxHashMap<Sym, String>,
-    /// Map from Minecraft ID (BG texture) for a machine to its human-readable name
-    machines: FxHashMap<Sym, String>,
+    /// Map from Minecraft ID (JEI category UID) for a machine to what we know
+    /// about it
+    machines: FxHashMap<Sym, MachineInfo>,
+    /// Map from Minecraft ID for a machine to the items that can perform its
+    /// recipes
+    machine_catalysts: FxHashMap<Sym, Vec<Sym>>,
+    /// Alternative sets shared between many slots. Slots refer to these by index.
+    ingredient_groups: Vec<IngredientGroup>,
+    /// How many recipe files were read
+    files: usize,
+    /// Recipe files that couldn't be read, along with why
+    failed_files: Vec<String>,
+}
+
+/// Configuration for writing a `RecipeDatabase` out to the database that
+/// doesn't come from the export
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// Filled fluid containers, and what they're equivalent to
+    pub fluid_containers: Vec<FluidContainer>,
+    /// Minecraft IDs (or base IDs) of the items to mark as raw materials
+    pub raw_items: Vec<String>,
+    /// Delete every recipe and machine before writing, so the database ends
+    /// up holding exactly this export
+    pub purge_first: bool,
+    /// Drop the foreign keys and indexes on the recipe tables while the
+    /// recipes go in. This makes a big import much faster, but locks the
+    /// tables until it's done, so it's only for offline imports.
+    pub drop_constraints: bool,
+}
+
+/// Everything we know about a machine, other than its ID
+pub struct MachineInfo {
+    pub human_name: String,
+    pub bg_texture: String,
 }
 
 impl RecipeDatabase {
     pub fn new() -> Self {
         RecipeDatabase {
             recipes: Vec::new(),
+            recipe_index: Default::default(),
+            duplicates: BTreeMap::new(),
+            removed: Vec::new(),
             interner: StringInterner::with_hasher(Default::default()),
             types_map: Default::default(),
             names_map: Default::default(),
             machines: Default::default(),
+            machine_catalysts: Default::default(),
+            ingredient_groups: Vec::new(),
+            files: 0,
+            failed_files: Vec::new(),
         }
     }
 
-    pub fn add_machine(&mut self, machine: Sym, name: String) {
-        self.machines.entry(machine).or_insert(name);
+    pub fn add_machine(&mut self, machine: Sym, human_name: String, bg_texture: String) {
+        self.machines.entry(machine).or_insert(MachineInfo {
+            human_name,
+            bg_texture,
+        });
     }
 
-    pub fn add_recipe(&mut self, recipe: Recipe) {
+    pub fn add_machine_catalyst(&mut self, machine: Sym, item: Sym) {
+        let catalysts = self.machine_catalysts.entry(machine).or_insert_with(Vec::new);
+        if !catalysts.contains(&item) {
+            catalysts.push(item);
+        }
+    }
+
+    /// Add a recipe, unless we already have a copy of it on the same machine.
+    /// Returns whether the recipe was new.
+    pub fn add_recipe(&mut self, mut recipe: Recipe) -> bool {
         assert!(self.machines.contains_key(&recipe.machine));
+        let key = self.normalize(&recipe);
+        if let Some(&existing) = self.recipe_index.get(&key) {
+            self.duplicates
+                .entry(existing)
+                .or_insert_with(Vec::new)
+                .push(recipe.machine);
+            return false;
+        }
+
+        recipe.content_hash = self.content_hash(&recipe);
+        self.recipe_index.insert(key, self.recipes.len());
         self.recipes.push(recipe);
+        true
+    }
+
+    fn normalize(&self, recipe: &Recipe) -> NormalizedRecipe {
+        fn sorted(components: &[RecipeComponent]) -> Vec<RecipeComponent> {
+            let mut components = components.to_vec();
+            components.sort_by_key(|x| (x.get_name(), x.get_quantity()));
+            components
+        }
+
+        let machine = match self.machine_catalysts.get(&recipe.machine) {
+            Some(catalysts) if !catalysts.is_empty() => {
+                let mut catalysts = catalysts.clone();
+                catalysts.sort();
+                MachineKey::Catalysts(catalysts)
+            }
+            _ => MachineKey::Category(recipe.machine),
+        };
+        let mut inputs: Vec<Vec<RecipeComponent>> = recipe
+            .inputs
+            .iter()
+            .map(|x| sorted(&x.allowed_elements))
+            .collect();
+        inputs.sort_by_key(|x| {
+            x.iter()
+                .map(|x| (x.get_name(), x.get_quantity()))
+                .collect::<Vec<_>>()
+        });
+
+        NormalizedRecipe {
+            machine,
+            inputs,
+            outputs: sorted(&recipe.outputs),
+        }
+    }
+
+    /// How many copies of recipes `add_recipe` dropped
+    pub fn num_duplicates(&self) -> usize {
+        self.duplicates.values().map(|x| x.len()).sum()
+    }
+
+    /// Every recipe that was added more than once
+    pub fn duplicates(&self) -> Vec<DuplicateRecipe> {
+        let resolve = |x: Sym| self.interner.resolve(x).unwrap_or("").to_string();
+        self.duplicates
+            .iter()
+            .map(|(&index, machines)| {
+                let recipe = &self.recipes[index];
+                DuplicateRecipe {
+                    machine: resolve(recipe.machine),
+                    outputs: recipe
+                        .outputs
+                        .iter()
+                        .map(|x| self.item_name(x.get_name()).to_owned())
+                        .collect(),
+                    copies_from: machines.iter().map(|x| resolve(*x)).collect(),
+                }
+            }).collect()
+    }
+
+    /// What was read, what failed, and what was merged
+    pub fn report(&self) -> ImportReport {
+        ImportReport {
+            files: self.files,
+            failed_files: self.failed_files.clone(),
+            recipes: self.num_recipes(),
+            duplicates_merged: self.num_duplicates(),
+            duplicates: self.duplicates(),
+        }
     }
 
     pub fn get_or_intern(&mut self, t: impl AsRef<str>) -> Sym {
         self.interner.get_or_intern(t.as_ref())
     }
 
+    /// Note that a recipe file was read, along with why it failed if it did
+    pub fn record_file(&mut self, failure: Option<String>) {
+        self.files += 1;
+        self.failed_files.extend(failure);
+    }
+
+    /// The name to give an item. Without tooltipMap.json (say, when only some
+    /// of the recipe files were given) the ID is the best name we have.
+    fn item_name(&self, item_id: Sym) -> &str {
+        match self.names_map.get(&item_id) {
+            Some(name) => name,
+            None => self.interner.resolve(item_id).unwrap_or(""),
+        }
+    }
+
     pub fn associate_name(&mut self, item_id: Sym, human_name: String) {
         self.names_map.entry(item_id).or_insert(human_name);
     }
@@ -57,71 +231,544 @@ impl RecipeDatabase {
         return self.recipes.len();
     }
 
-    pub fn insert_items(&self, conn: &PgConnection) {
-        use self::schema::items;
+    /// Apply hand-written changes to the recipes. This should be run right
+    /// after ingesting, so that added recipes get the same treatment as the
+    /// ones from JEI.
+    pub fn apply_overrides(&mut self, overrides: &[RecipeOverride]) {
+        let mut added = 0;
+        let mut removed = 0;
+        for entry in overrides {
+            match entry {
+                RecipeOverride::Add(recipe) => {
+                    if self.add_manual_recipe(recipe) {
+                        added += 1;
+                    }
+                }
+                RecipeOverride::Remove { output, machine } => {
+                    removed += self.remove_recipes(output, machine.as_ref().map(|x| x.as_str()));
+                }
+                RecipeOverride::Replace(recipe) => {
+                    if let Some(output) = recipe.outputs.first() {
+                        removed += self.remove_recipes(&output.item, Some(&recipe.machine));
+                    }
+                    if self.add_manual_recipe(recipe) {
+                        added += 1;
+                    }
+                }
+            }
+        }
+
+        info!(
+            "Recipe overrides added {} recipes and removed {}",
+            added, removed
+        );
+    }
+
+    fn add_manual_recipe(&mut self, manual: &ManualRecipe) -> bool {
+        let machine = match self.interner.get(manual.machine.as_str()) {
+            Some(machine) if self.machines.contains_key(&machine) => machine,
+            _ => {
+                warn!(
+                    "Skipping a recipe override for unknown machine {}",
+                    manual.machine
+                );
+                return false;
+            }
+        };
+
+        let mut recipe = Recipe::new(machine);
+        for alternatives in &manual.inputs {
+            let mut slot = CraftingSlot::new();
+            slot.allowed_elements = alternatives
+                .iter()
+                .map(|x| self.manual_component(x))
+                .collect();
+            recipe.inputs.push(slot);
+        }
+        recipe.outputs = manual
+            .outputs
+            .iter()
+            .map(|x| self.manual_component(x))
+            .collect();
+
+        self.add_recipe(recipe)
+    }
+
+    fn manual_component(&mut self, stack: &ManualStack) -> RecipeComponent {
+        let name = self.get_or_intern(stack.item.as_str());
+        let quantity = stack.quantity.max(0) as u32;
+        // Items JEI never showed don't have a tooltip to take the name from
+        self.associate_name(name, stack.item.clone());
+        if stack.fluid {
+            self.associate_type(name, ItemType::Fluid);
+            RecipeComponent::Fluid {
+                amount: quantity,
+                name,
+            }
+        } else {
+            self.associate_type(name, ItemType::Item);
+            RecipeComponent::ItemStack {
+                count: quantity,
+                name,
+            }
+        }
+    }
 
+    /// Remove every recipe that makes `output`, on `machine` if it's given.
+    /// Returns how many recipes were removed.
+    fn remove_recipes(&mut self, output: &str, machine: Option<&str>) -> usize {
+        let output = match self.interner.get(output) {
+            Some(output) => output,
+            None => return 0,
+        };
+        let machine = match machine {
+            Some(machine) => match self.interner.get(machine) {
+                Some(machine) => Some(machine),
+                None => return 0,
+            },
+            None => None,
+        };
+
+        let keep: Vec<bool> = self
+            .recipes
+            .iter()
+            .map(|recipe| {
+                let on_machine = machine.map(|x| x == recipe.machine).unwrap_or(true);
+                !(on_machine && recipe.outputs.iter().any(|x| x.get_name() == output))
+            }).collect();
+        let removed = keep.iter().filter(|x| !**x).count();
+        if removed == 0 {
+            return 0;
+        }
+
+        // Recipes after the removed ones move down, so the indices we keep for
+        // deduplication have to move with them
+        let mut new_index = Vec::with_capacity(keep.len());
+        let mut next = 0;
+        for kept in &keep {
+            if *kept {
+                new_index.push(Some(next));
+                next += 1;
+            } else {
+                new_index.push(None);
+            }
+        }
+        self.removed.extend(
+            self.recipes
+                .iter()
+                .zip(keep.iter())
+                .filter(|(_, kept)| !**kept)
+                .map(|(recipe, _)| recipe.content_hash.clone()),
+        );
+        let mut i = 0;
+        self.recipes.retain(|_| {
+            i += 1;
+            keep[i - 1]
+        });
+        self.recipe_index = self
+            .recipe_index
+            .drain()
+            .filter_map(|(key, index)| new_index[index].map(|x| (key, x)))
+            .collect();
+        self.duplicates = std::mem::replace(&mut self.duplicates, BTreeMap::new())
+            .into_iter()
+            .filter_map(|(index, machines)| new_index[index].map(|x| (x, machines)))
+            .collect();
+
+        removed
+    }
+
+    /// Describe the recipes we have in a way that can be compared with
+    /// another export or a database
+    pub fn snapshot(&self) -> Snapshot {
+        let resolve = |x: Sym| self.interner.resolve(x).unwrap_or("").to_string();
+        let stack = |x: &RecipeComponent| Stack {
+            minecraft_id: resolve(x.get_name()),
+            quantity: x.get_quantity(),
+        };
+
+        Snapshot {
+            items: self
+                .types_map
+                .keys()
+                .map(|x| {
+                    (resolve(*x), self.item_name(*x).to_owned())
+                }).collect(),
+            machines: self
+                .machines
+                .iter()
+                .map(|(uid, info)| (resolve(*uid), info.human_name.clone()))
+                .collect(),
+            recipes: self
+                .recipes
+                .iter()
+                .map(|recipe| {
+                    SnapshotRecipe::new(
+                        resolve(recipe.machine),
+                        recipe
+                            .inputs
+                            .iter()
+                            .filter(|x| !x.allowed_elements.is_empty())
+                            .map(|x| x.allowed_elements.iter().map(&stack).collect())
+                            .collect(),
+                        recipe.outputs.iter().map(&stack).collect(),
+                    )
+                }).collect(),
+        }
+    }
+
+    /// Decide which recipe inputs are tools or catalysts rather than
+    /// ingredients. Inputs that are handed back by the recipe are removed from
+    /// its outputs, since the recipe doesn't really produce them. This should
+    /// be run after all the tooltips have been loaded.
+    pub fn mark_unconsumed_inputs(&mut self, overrides: &[ConsumptionOverride]) {
+        let overrides: Vec<(Sym, Option<&str>, bool)> = overrides
+            .iter()
+            .filter_map(|x| {
+                self.interner
+                    .get(x.item.as_str())
+                    .map(|item| (item, x.machine.as_ref().map(|x| x.as_str()), x.consumed))
+            }).collect();
+
+        let mut marked = 0;
+        for recipe in &mut self.recipes {
+            let machine_name = self.machines[&recipe.machine].human_name.as_str();
+            let mut returned = Vec::new();
+            let outputs = &recipe.outputs;
+            for slot in &mut recipe.inputs {
+                if slot.allowed_elements.is_empty() {
+                    continue;
+                }
+
+                let is_returned = slot
+                    .allowed_elements
+                    .iter()
+                    .all(|x| outputs.contains(x));
+                let names_map = &self.names_map;
+                let is_tool = slot.allowed_elements.iter().all(|x| {
+                    names_map
+                        .get(&x.get_name())
+                        .map(|x| consumption::looks_like_tool(x))
+                        .unwrap_or(false)
+                });
+
+                slot.consumed = !(is_returned || is_tool);
+                for &(item, machine, consumed) in &overrides {
+                    if machine.map(|x| x == machine_name).unwrap_or(true)
+                        && slot.allowed_elements.iter().any(|x| x.get_name() == item)
+                    {
+                        slot.consumed = consumed;
+                    }
+                }
+
+                if is_returned && !slot.consumed {
+                    returned.extend(slot.allowed_elements.iter().cloned());
+                }
+                if !slot.consumed {
+                    marked += 1;
+                }
+            }
+
+            if !returned.is_empty() && recipe.outputs.iter().any(|x| !returned.contains(x)) {
+                recipe.outputs.retain(|x| !returned.contains(x));
+            }
+        }
+
+        info!("Marked {} input slots as not consumed", marked);
+    }
+
+    /// Find sets of alternatives that show up in many slots and turn them into
+    /// ingredient groups. This should be run after all the tooltips have been
+    /// loaded, since the group names are derived from the member names.
+    pub fn detect_ingredient_groups(&mut self) {
+        fn sorted_elements(elements: &[RecipeComponent]) -> Vec<RecipeComponent> {
+            let mut elements = elements.to_vec();
+            elements.sort_by_key(|x| (x.get_name(), x.get_quantity()));
+            elements
+        }
+
+        let mut uses: FxHashMap<Vec<RecipeComponent>, usize> = Default::default();
+        for recipe in &self.recipes {
+            for slot in &recipe.inputs {
+                // Tools stay as they are, so the consumed flag stays with them
+                if slot.allowed_elements.len() < MIN_GROUP_SIZE || !slot.consumed {
+                    continue;
+                }
+                *uses.entry(sorted_elements(&slot.allowed_elements)).or_insert(0) += 1;
+            }
+        }
+
+        let mut group_ids: FxHashMap<Vec<RecipeComponent>, usize> = Default::default();
+        for (members, count) in uses.into_iter() {
+            if count < MIN_GROUP_USES {
+                continue;
+            }
+            let name = {
+                let names: Vec<&str> = members
+                    .iter()
+                    .map(|x| self.item_name(x.get_name()))
+                    .collect();
+                group_name(&names)
+            };
+            group_ids.insert(members.clone(), self.ingredient_groups.len());
+            self.ingredient_groups.push(IngredientGroup { name, members });
+        }
+
+        for recipe in &mut self.recipes {
+            for slot in &mut recipe.inputs {
+                if slot.allowed_elements.len() < MIN_GROUP_SIZE || !slot.consumed {
+                    continue;
+                }
+                slot.group = group_ids
+                    .get(&sorted_elements(&slot.allowed_elements))
+                    .cloned();
+            }
+        }
+
+        info!(
+            "Found {} ingredient groups",
+            self.ingredient_groups.len()
+        );
+    }
+
+    /// Write everything out to the database. Recipes that are already there
+    /// are updated in place and anything the export doesn't mention is left
+    /// alone, unless `options.purge_first` asks for a clean slate. It all
+    /// happens in one transaction, so a failed write leaves the database as
+    /// it was.
+    pub fn write(&self, conn: &PgConnection, options: &WriteOptions) -> Result<(), MCCraftError> {
+        self.write_with_progress(conn, options, &mut LogProgress)
+    }
+
+    pub fn write_with_progress(
+        &self,
+        conn: &PgConnection,
+        options: &WriteOptions,
+        progress: &mut dyn Progress,
+    ) -> Result<(), MCCraftError> {
+        conn.transaction::<_, MCCraftError, _>(|| {
+            if options.purge_first {
+                progress.log("Deleting the recipes that are already in the database".to_owned());
+                ::purge(conn)?;
+            }
+
+            progress.stage(Stage::InsertingItems);
+            self.insert_items(conn)?;
+            self.mark_raw_items(conn, &options.raw_items)?;
+
+            progress.stage(Stage::InsertingFluidContainers);
+            self.insert_fluid_containers(conn, &options.fluid_containers)?;
+
+            progress.stage(Stage::InsertingRecipes);
+            self.insert_recipes(conn, options.drop_constraints)?;
+
+            Ok(())
+        })
+    }
+
+    fn insert_items(&self, conn: &PgConnection) -> QueryResult<()> {
         let mut total_inserted = 0;
-        let mut new_items = Vec::new();
+        // Items without a tooltip are named after their IDs, which shouldn't
+        // replace the real names an earlier import gave them
+        let mut named = Vec::new();
+        let mut unnamed = Vec::new();
         info!("Begin item list build");
         for (item, ty) in self.types_map.iter() {
             let minecraft_id = self
                 .interner
                 .resolve(*item)
                 .expect("String interner desynced");
-            let human_name = &self.names_map[item];
+            let human_name = self.item_name(*item);
+            let parsed = MinecraftId::parse(minecraft_id);
             let ins = sql::NewItem {
                 human_name,
                 minecraft_id,
                 ty: *ty,
+                base_id: parsed.base_id,
+                meta: parsed.meta,
+                nbt_hash: parsed.nbt_hash,
             };
+            let has_name = self.names_map.contains_key(item);
+            let new_items = if has_name { &mut named } else { &mut unnamed };
             new_items.push(ins);
             if new_items.len() == 5000 {
                 // Batch in units of 5000
                 info!("Ending batch.");
-                let inserted = diesel::insert_into(items::table)
-                    .values(&new_items)
-                    .on_conflict_do_nothing()
-                    .execute(conn)
-                    .expect("Failed to submit batch to database");
-                info!("Inserted {} new items", inserted);
+                let inserted = Self::upsert_items(conn, new_items, has_name)?;
+                info!("Inserted or updated {} items", inserted);
                 total_inserted += inserted;
                 new_items.clear();
             }
         }
         info!("Item list build complete, beginning insert");
-        total_inserted += diesel::insert_into(items::table)
-            .values(&new_items)
-            .on_conflict_do_nothing()
-            .execute(conn)
-            .expect("Failed to insert into database");
+        total_inserted += Self::upsert_items(conn, &named, true)?;
+        total_inserted += Self::upsert_items(conn, &unnamed, false)?;
         info!(
-            "Insertion complete. Inserted {} new items of {} known",
+            "Insertion complete. Inserted or updated {} items of {} known",
             total_inserted,
             self.types_map.len()
         );
+
+        Ok(())
+    }
+
+    /// Insert a batch of items. Items that already exist are brought up to
+    /// date with the export, including their names if `update_names` is set.
+    fn upsert_items(
+        conn: &PgConnection,
+        new_items: &[sql::NewItem],
+        update_names: bool,
+    ) -> QueryResult<usize> {
+        use self::schema::items;
+
+        if new_items.is_empty() {
+            return Ok(0);
+        }
+        let insert = diesel::insert_into(items::table)
+            .values(new_items)
+            .on_conflict(items::minecraft_id)
+            .do_update();
+        if update_names {
+            insert
+                .set((
+                    items::human_name.eq(excluded(items::human_name)),
+                    items::ty.eq(excluded(items::ty)),
+                    items::base_id.eq(excluded(items::base_id)),
+                    items::meta.eq(excluded(items::meta)),
+                    items::nbt_hash.eq(excluded(items::nbt_hash)),
+                )).execute(conn)
+        } else {
+            insert
+                .set((
+                    items::ty.eq(excluded(items::ty)),
+                    items::base_id.eq(excluded(items::base_id)),
+                    items::meta.eq(excluded(items::meta)),
+                    items::nbt_hash.eq(excluded(items::nbt_hash)),
+                )).execute(conn)
+        }
+    }
+
+    /// Flag the items that are gathered rather than crafted. Entries in
+    /// `raw_items` can be full IDs or base IDs, which cover every variant.
+    fn mark_raw_items(&self, conn: &PgConnection, raw_items: &[String]) -> QueryResult<()> {
+        use self::schema::items::dsl::*;
+
+        let marked = conn
+            .transaction(|| {
+                diesel::update(items).set(raw.eq(false)).execute(conn)?;
+                diesel::update(
+                    items.filter(
+                        minecraft_id
+                            .eq_any(raw_items)
+                            .or(base_id.eq_any(raw_items)),
+                    ),
+                ).set(raw.eq(true))
+                .execute(conn)
+            })?;
+        info!("Marked {} items as raw", marked);
+
+        Ok(())
     }
 
-    pub fn insert_recipes(&self, conn: &PgConnection) {
-        let machines = self.insert_machines(conn);
+    /// Record which items are filled fluid containers. This needs the items to
+    /// have been inserted already. Containers that refer to items we've never
+    /// seen are skipped.
+    fn insert_fluid_containers(
+        &self,
+        conn: &PgConnection,
+        containers: &[FluidContainer],
+    ) -> QueryResult<()> {
+        use self::schema::{fluid_containers, items};
+
+        let mcids: Vec<&str> = containers
+            .iter()
+            .flat_map(|x| {
+                vec![Some(&x.filled), Some(&x.fluid), x.empty.as_ref()]
+                    .into_iter()
+                    .filter_map(|x| x.map(|x| x.as_str()))
+            }).collect();
+        let item_ids: FxHashMap<String, i32> = items::table
+            .select((items::minecraft_id, items::id))
+            .filter(items::minecraft_id.eq_any(&mcids))
+            .load::<(String, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        let mut to_insert = Vec::new();
+        for container in containers {
+            let empty_item = match container.empty {
+                Some(ref empty) => match item_ids.get(empty) {
+                    Some(empty) => Some(*empty),
+                    None => {
+                        info!("Skipping fluid container {}: unknown empty container {}", container.filled, empty);
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            match (item_ids.get(&container.filled), item_ids.get(&container.fluid)) {
+                (Some(filled_item), Some(fluid)) => to_insert.push(sql::NewFluidContainer {
+                    filled_item: *filled_item,
+                    fluid: *fluid,
+                    amount: container.amount,
+                    empty_item,
+                }),
+                _ => info!("Skipping fluid container {}: item or fluid not in this pack", container.filled),
+            }
+        }
+
+        let inserted = diesel::insert_into(fluid_containers::table)
+            .values(&to_insert)
+            .on_conflict(fluid_containers::filled_item)
+            .do_update()
+            .set((
+                fluid_containers::fluid.eq(excluded(fluid_containers::fluid)),
+                fluid_containers::amount.eq(excluded(fluid_containers::amount)),
+                fluid_containers::empty_item.eq(excluded(fluid_containers::empty_item)),
+            )).execute(conn)?;
+        info!(
+            "Fluid container insert completed. {} of {} configured",
+            inserted,
+            containers.len()
+        );
+
+        Ok(())
+    }
 
-        let recipe_ids = self.do_primary_recipe_insert(conn, &machines);
+    fn insert_recipes(&self, conn: &PgConnection, drop_constraints: bool) -> QueryResult<()> {
+        let machines = self.insert_machines(conn)?;
+
+        let recipe_ids = self.do_primary_recipe_insert(conn, &machines)?;
+
+        let removed = self.remove_legacy_machines(conn)?;
+        if removed > 0 {
+            info!("Removed {} machines that were keyed by their texture", removed);
+        }
+
+        let hidden = self.hide_removed_recipes(conn)?;
+        info!("Hid {} recipes removed by overrides", hidden);
 
         let mut item_cache = Default::default();
 
-        // Temporarily drop foreign keys.
-        self.disable_recipe_fks(conn).expect("Failed to disable foreign keys");
+        self.insert_machine_catalysts(conn, &mut item_cache, &machines)?;
+
+        let groups = self.insert_ingredient_groups(conn, &mut item_cache)?;
+
+        // Temporarily drop foreign keys. This is DDL, so it's rolled back along
+        // with everything else if the write fails.
+        if drop_constraints {
+            self.disable_recipe_fks(conn)?;
+        }
 
         info!("Inserting individual recipes");
 
         let mut counter = 0;
         for (i, (recipe, id)) in self.recipes.iter().zip(recipe_ids.iter()).enumerate() {
-            counter += self
-                .insert_recipe_inputs(conn, &mut item_cache, *id, recipe)
-                .expect(&format!("Failed to insert inputs for recipe {:?}", recipe));
-            counter += self
-                .insert_recipe_outputs(conn, &mut item_cache, *id, recipe)
-                .expect(&format!("Failed to insert outputs for recipe {:?}", recipe));
-            if i % (self.recipes.len() / 100) == 0 {
+            if let Some(id) = *id {
+                counter += self.insert_recipe_inputs(conn, &mut item_cache, &groups, id, recipe)?;
+                counter += self.insert_recipe_outputs(conn, &mut item_cache, id, recipe)?;
+            }
+            if i % (self.recipes.len() / 100).max(1) == 0 {
                 info!(
                     "Inserted {} / {} recipes ({} items)",
                     i,
@@ -131,11 +778,15 @@ impl RecipeDatabase {
             }
         }
 
-        self.enable_recipe_fks(conn).expect("Failed to reenable foreign keys");
+        if drop_constraints {
+            self.enable_recipe_fks(conn)?;
+        }
+
+        Ok(())
     }
 
     fn disable_recipe_fks(&self, conn: &PgConnection) -> QueryResult<()> {
-        warn!("Disabling a bunch of constraints. If the import dies at this point, you'll probably have to restart from scratch.");
+        info!("Disabling the recipe constraints until the recipes are in");
         // Drop foreign key constraints
         diesel::sql_query("ALTER TABLE mccraft.input_slots DROP CONSTRAINT input_slots_for_recipe_fkey").execute(conn)?;
         diesel::sql_query("ALTER TABLE mccraft.crafting_components DROP CONSTRAINT crafting_components_crafting_slot_fkey").execute(conn)?;
@@ -165,13 +816,63 @@ impl RecipeDatabase {
         diesel::sql_query("CREATE INDEX crafting_component_slot ON crafting_components USING hash (crafting_slot)").execute(conn)?;
         diesel::sql_query("CREATE INDEX output_item ON outputs USING hash (item)").execute(conn)?;
 
-        warn!("Constraints readded. The database should now be in a consistent state");
+        info!("Constraints readded");
 
         Ok(())
     }
 
+    /// Machines used to be keyed by their background texture, so one row could
+    /// hold the recipes of several categories. Every category now has its own
+    /// row, and `do_primary_recipe_insert` moves recipes it knows to theirs.
+    /// This gets rid of what's left on the old rows: recipes from before
+    /// content hashes, which this import has replaced, and then any old row
+    /// with no recipes left. Returns how many machines were removed.
+    fn remove_legacy_machines(&self, conn: &PgConnection) -> QueryResult<usize> {
+        use self::schema::{machine_catalysts, machines, recipes};
+
+        let uids: FxHashSet<&str> = self
+            .machines
+            .keys()
+            .map(|x| self.interner.resolve(*x).unwrap())
+            .collect();
+        let textures: Vec<&str> = self
+            .machines
+            .values()
+            .map(|x| x.bg_texture.as_str())
+            .filter(|x| !uids.contains(x))
+            .collect();
+        let legacy: Vec<i32> = machines::table
+            .select(machines::id)
+            .filter(machines::minecraft_id.eq_any(&textures))
+            .load(conn)?;
+        if legacy.is_empty() {
+            return Ok(0);
+        }
+
+        let stale: Vec<i32> = recipes::table
+            .select(recipes::id)
+            .filter(recipes::machine.eq_any(&legacy))
+            .filter(recipes::content_hash.is_null())
+            .load(conn)?;
+        self.clear_recipe_contents(conn, &stale)?;
+        diesel::delete(recipes::table.filter(recipes::id.eq_any(&stale))).execute(conn)?;
+
+        // Recipes added by hand stay where they are, and keep their machine
+        let in_use: FxHashSet<i32> = recipes::table
+            .select(recipes::machine)
+            .filter(recipes::machine.eq_any(&legacy))
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+        let empty: Vec<i32> = legacy.into_iter().filter(|x| !in_use.contains(x)).collect();
+        diesel::delete(
+            machine_catalysts::table.filter(machine_catalysts::machine.eq_any(&empty)),
+        ).execute(conn)?;
+        diesel::delete(machines::table.filter(machines::id.eq_any(&empty))).execute(conn)
+    }
+
     // Insert machines, returning a mapping from machine name symbol to ID in the DB
-    fn insert_machines(&self, conn: &PgConnection) -> FxHashMap<Sym, i32> {
+    fn insert_machines(&self, conn: &PgConnection) -> QueryResult<FxHashMap<Sym, i32>> {
         use self::schema::machines::dsl::*;
         // There are relatively few machines so we don't bother with batching
         info!("Preparing to insert machines");
@@ -179,19 +880,23 @@ impl RecipeDatabase {
             let to_insert: Vec<_> = self
                 .machines
                 .iter()
-                .map(|(mcid, name)| sql::NewMachine {
-                    human_name: name,
+                .map(|(mcid, info)| sql::NewMachine {
+                    human_name: &info.human_name,
                     minecraft_id: self.interner.resolve(*mcid).unwrap(),
+                    bg_texture: &info.bg_texture,
                 })
                 .collect();
             diesel::insert_into(machines)
                 .values(&to_insert)
-                .on_conflict_do_nothing()
-                .execute(conn)
-                .expect("Failed to insert machines")
+                .on_conflict(minecraft_id)
+                .do_update()
+                .set((
+                    human_name.eq(excluded(human_name)),
+                    bg_texture.eq(excluded(bg_texture)),
+                )).execute(conn)?
         };
         info!(
-            "Machine insert completed. {} new of {} known",
+            "Machine insert completed. {} inserted or updated of {} known",
             inserted,
             self.machines.len()
         );
@@ -202,51 +907,259 @@ impl RecipeDatabase {
         // Go one at a time because finding a clever way to do this seems hard
         for mcid in self.machines.keys() {
             let machine_id = self.interner.resolve(*mcid).unwrap();
-            let machine_id: Vec<i32> = machines
+            let machine_id: i32 = machines
                 .select(id)
                 .filter(minecraft_id.eq(machine_id))
-                .load::<i32>(conn)
-                .expect(&format!("Failed to load ID for machine {:?}", minecraft_id));
-            assert!(machine_id.len() == 1);
-            machine_ids.entry(*mcid).or_insert(machine_id[0]);
+                .first(conn)?;
+            machine_ids.entry(*mcid).or_insert(machine_id);
         }
         info!("Machines retrieved");
 
-        machine_ids
+        Ok(machine_ids)
     }
 
-    /// Returns a vector of recipe IDs in the database, in the same order as the
-    /// recipes appear in our internal list.
+    fn insert_machine_catalysts(
+        &self,
+        conn: &PgConnection,
+        item_cache: &mut FxHashMap<Sym, i32>,
+        machines: &FxHashMap<Sym, i32>,
+    ) -> QueryResult<()> {
+        use self::schema::machine_catalysts;
+
+        let to_insert: Vec<_> = self
+            .machine_catalysts
+            .iter()
+            .flat_map(|(machine, catalysts)| {
+                catalysts.iter().map(move |catalyst| (machine, catalyst))
+            }).map(|(machine, catalyst)| {
+                Ok(sql::NewMachineCatalyst {
+                    machine: machines[machine],
+                    item: self.get_item_id(conn, item_cache, *catalyst)?,
+                })
+            }).collect::<QueryResult<_>>()?;
+
+        let inserted = diesel::insert_into(machine_catalysts::table)
+            .values(&to_insert)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        info!(
+            "Machine catalyst insert completed. {} new of {} known",
+            inserted,
+            to_insert.len()
+        );
+
+        Ok(())
+    }
+
+    /// A hash of everything that identifies a recipe, which stays the same
+    /// across imports, unlike the recipe's ID
+    fn content_hash(&self, recipe: &Recipe) -> String {
+        let stack = |c: &RecipeComponent| HashedStack {
+            fluid: match c {
+                RecipeComponent::ItemStack { .. } => false,
+                RecipeComponent::Fluid { .. } => true,
+            },
+            name: self.interner.resolve(c.get_name()).unwrap(),
+            quantity: c.get_quantity(),
+        };
+
+        let inputs: Vec<Vec<HashedStack>> = recipe
+            .inputs
+            .iter()
+            .map(|slot| slot.allowed_elements.iter().map(&stack).collect())
+            .collect();
+        let outputs: Vec<HashedStack> = recipe.outputs.iter().map(&stack).collect();
+        recipe_hash::content_hash(
+            self.interner.resolve(recipe.machine).unwrap(),
+            &inputs,
+            &outputs,
+        )
+    }
+
+    /// Returns the database ID of each recipe, in the same order as the
+    /// recipes appear in our internal list. Recipes that were already in the
+    /// database are moved to the machine we have for them now, and have their
+    /// slots and outputs cleared so they can be filled in again. Exact copies
+    /// of an earlier recipe in the list get `None` since there is nothing more
+    /// to insert for them.
     fn do_primary_recipe_insert(
         &self,
         conn: &PgConnection,
         machines: &FxHashMap<Sym, i32>,
-    ) -> Vec<i32> {
+    ) -> QueryResult<Vec<Option<i32>>> {
         info!("Performing primary recipe insert");
         use self::schema::recipes::dsl::*;
+
+        let mut seen = FxHashSet::default();
         let ins: Vec<_> = self
             .recipes
             .iter()
+            .filter(|r| seen.insert(r.content_hash.as_str()))
             .map(|r| sql::NewRecipe {
                 machine: machines[&r.machine],
+                content_hash: &r.content_hash,
             })
             .collect();
 
-        ins.chunks(8192)
-            .flat_map(|ins_chunk| {
+        let mut existing = 0;
+        let mut ids: FxHashMap<String, i32> = FxHashMap::default();
+        for ins_chunk in ins.chunks(8192) {
+            let hashes: Vec<&str> = ins_chunk.iter().map(|x| x.content_hash).collect();
+            let present: Vec<i32> = recipes
+                .select(id)
+                .filter(content_hash.eq_any(&hashes))
+                .load(conn)?;
+            existing += self.clear_recipe_contents(conn, &present)?;
+
+            ids.extend(
                 diesel::insert_into(recipes)
                     .values(ins_chunk)
-                    .returning(id)
-                    .get_results(conn)
-                    .expect("Failed to create recipe IDs")
-            })
-            .collect()
+                    .on_conflict(content_hash)
+                    .do_update()
+                    .set(machine.eq(excluded(machine)))
+                    .returning((content_hash, id))
+                    .get_results::<(Option<String>, i32)>(conn)?
+                    .into_iter()
+                    .filter_map(|(hash, rid)| hash.map(|hash| (hash, rid))),
+            );
+        }
+        info!(
+            "Primary recipe insert completed. {} new, {} updated, {} duplicates skipped",
+            ins.len() - existing,
+            existing,
+            self.recipes.len() - ins.len()
+        );
+
+        // Only the first recipe with a given hash gets the ID
+        Ok(self
+            .recipes
+            .iter()
+            .map(|r| ids.remove(&r.content_hash))
+            .collect())
+    }
+
+    /// Hide the rows of recipes that overrides removed, so that they don't
+    /// linger from imports made before the override was written. Recipes that
+    /// an override put back unchanged stay visible.
+    fn hide_removed_recipes(&self, conn: &PgConnection) -> QueryResult<usize> {
+        use self::schema::recipes::dsl::*;
+
+        let kept: FxHashSet<&str> = self.recipes.iter().map(|x| x.content_hash.as_str()).collect();
+        let removed: Vec<&str> = self
+            .removed
+            .iter()
+            .map(|x| x.as_str())
+            .filter(|x| !kept.contains(x))
+            .collect();
+
+        let mut hidden_rows = 0;
+        for chunk in removed.chunks(8192) {
+            hidden_rows += diesel::update(recipes.filter(content_hash.eq_any(chunk)))
+                .set(hidden.eq(true))
+                .execute(conn)?;
+        }
+        Ok(hidden_rows)
+    }
+
+    /// Remove the slots, components and outputs of the given recipes, leaving
+    /// the recipe rows themselves. Returns how many recipes were cleared.
+    fn clear_recipe_contents(&self, conn: &PgConnection, rids: &[i32]) -> QueryResult<usize> {
+        use self::schema::{crafting_components, input_slots, outputs};
+
+        if rids.is_empty() {
+            return Ok(0);
+        }
+
+        let slots = input_slots::table
+            .select(input_slots::id)
+            .filter(input_slots::for_recipe.eq_any(rids));
+        diesel::delete(
+            crafting_components::table.filter(crafting_components::crafting_slot.eq_any(slots)),
+        ).execute(conn)?;
+        diesel::delete(input_slots::table.filter(input_slots::for_recipe.eq_any(rids)))
+            .execute(conn)?;
+        diesel::delete(outputs::table.filter(outputs::recipe.eq_any(rids))).execute(conn)?;
+
+        Ok(rids.len())
+    }
+
+    /// Insert the ingredient groups, returning their IDs in the database in the
+    /// same order as our internal list.
+    fn insert_ingredient_groups(
+        &self,
+        conn: &PgConnection,
+        item_cache: &mut FxHashMap<Sym, i32>,
+    ) -> QueryResult<Vec<i32>> {
+        use self::schema::{ingredient_group_members, ingredient_groups};
+
+        info!("Inserting {} ingredient groups", self.ingredient_groups.len());
+        let signatures: Vec<String> = self
+            .ingredient_groups
+            .iter()
+            .map(|group| {
+                group
+                    .members
+                    .iter()
+                    .map(|x| {
+                        format!(
+                            "{}*{}",
+                            self.interner.resolve(x.get_name()).unwrap(),
+                            x.get_quantity()
+                        )
+                    }).collect::<Vec<_>>()
+                    .join(",")
+            }).collect();
+
+        let mut new_groups = 0;
+        for (group, signature) in self.ingredient_groups.iter().zip(signatures.iter()) {
+            let inserted: Vec<i32> = diesel::insert_into(ingredient_groups::table)
+                .values(&sql::NewIngredientGroup {
+                    human_name: &group.name,
+                    signature,
+                }).on_conflict_do_nothing()
+                .returning(ingredient_groups::id)
+                .get_results(conn)?;
+
+            // Only fill in the members for groups we just created. Existing
+            // groups already have them.
+            if let Some(gid) = inserted.get(0) {
+                let members: Vec<_> = group
+                    .members
+                    .iter()
+                    .map(|member| {
+                        Ok(sql::NewIngredientGroupMember {
+                            ingredient_group: *gid,
+                            item: self.get_item_id(conn, item_cache, member.get_name())?,
+                            quantity: member.get_quantity(),
+                        })
+                    }).collect::<QueryResult<_>>()?;
+                diesel::insert_into(ingredient_group_members::table)
+                    .values(&members)
+                    .execute(conn)?;
+                new_groups += 1;
+            }
+        }
+        info!(
+            "Ingredient group insert completed. {} new of {} known",
+            new_groups,
+            self.ingredient_groups.len()
+        );
+
+        let ids: FxHashMap<String, i32> = ingredient_groups::table
+            .select((ingredient_groups::signature, ingredient_groups::id))
+            .filter(ingredient_groups::signature.eq_any(&signatures))
+            .load::<(String, i32)>(conn)?
+            .into_iter()
+            .collect();
+
+        Ok(signatures.iter().map(|x| ids[x]).collect())
     }
 
     fn insert_recipe_inputs(
         &self,
         conn: &PgConnection,
         item_cache: &mut FxHashMap<Sym, i32>,
+        groups: &[i32],
         rid: i32,
         recipe: &Recipe,
     ) -> QueryResult<(usize)> {
@@ -254,26 +1167,39 @@ impl RecipeDatabase {
 
         let mut inserted_items = 0;
         let slots: Vec<sql::InputSlot> = {
-            let slots: Vec<_> = recipe.inputs.iter().map(|_| sql::NewInputSlot { for_recipe: rid } ).collect();
+            let slots: Vec<_> = recipe
+                .inputs
+                .iter()
+                .map(|slot| sql::NewInputSlot {
+                    for_recipe: rid,
+                    ingredient_group: slot.group.map(|x| groups[x]),
+                    x: slot.position.map(|x| x.0),
+                    y: slot.position.map(|x| x.1),
+                }).collect();
             diesel::insert_into(input_slots::table)
                 .values(slots)
-                .get_results(conn)
-                .expect("Failed to create input slots")
+                .get_results(conn)?
         };
         let slots: Vec<i32> = slots.into_iter().map(|x| *x.id()).collect();
 
         for (i, slot) in recipe.inputs.iter().enumerate() {
             let slot_id = slots[i];
+            if slot.group.is_some() {
+                // The group already lists the alternatives
+                continue;
+            }
 
             let ins: Vec<_> = slot
                 .allowed_elements
                 .iter()
-                .map(|elem| sql::NewCraftingComponent {
-                    crafting_slot: slot_id,
-                    quantity: elem.get_quantity(),
-                    item: self.get_item_id(conn, item_cache, elem.get_name()),
-                })
-                .collect();
+                .map(|elem| {
+                    Ok(sql::NewCraftingComponent {
+                        crafting_slot: slot_id,
+                        quantity: elem.get_quantity(),
+                        item: self.get_item_id(conn, item_cache, elem.get_name())?,
+                        consumed: slot.consumed,
+                    })
+                }).collect::<QueryResult<_>>()?;
 
             inserted_items += diesel::insert_into(crafting_components::table)
                 .values(ins)
@@ -295,28 +1221,275 @@ impl RecipeDatabase {
         let ins: Vec<_> = target
             .outputs
             .iter()
-            .map(|output| sql::NewOutput {
-                recipe: rid,
-                quantity: output.get_quantity(),
-                item: self.get_item_id(conn, item_cache, output.get_name()),
-            })
-            .collect();
+            .map(|output| {
+                Ok(sql::NewOutput {
+                    recipe: rid,
+                    quantity: output.get_quantity(),
+                    item: self.get_item_id(conn, item_cache, output.get_name())?,
+                })
+            }).collect::<QueryResult<_>>()?;
 
         diesel::insert_into(outputs).values(&ins).execute(conn)
     }
 
-    fn get_item_id(&self, conn: &PgConnection, cache: &mut FxHashMap<Sym, i32>, mcid: Sym) -> i32 {
+    fn get_item_id(
+        &self,
+        conn: &PgConnection,
+        cache: &mut FxHashMap<Sym, i32>,
+        mcid: Sym,
+    ) -> QueryResult<i32> {
         use self::schema::items::dsl::*;
 
-        *cache.entry(mcid).or_insert_with(|| {
-            let mcid = self.interner.resolve(mcid).unwrap();
-            *items
-                .select(id)
-                .filter(minecraft_id.eq(mcid))
-                .load::<i32>(conn)
-                .expect(&format!("Failed to retrieve item ID for {:?}", mcid))
-                .get(0)
-                .expect(&format!("No results for mcid {:?}", mcid))
-        })
+        if let Some(item_id) = cache.get(&mcid) {
+            return Ok(*item_id);
+        }
+        let item_id = items
+            .select(id)
+            .filter(minecraft_id.eq(self.interner.resolve(mcid).unwrap()))
+            .first(conn)?;
+        cache.insert(mcid, item_id);
+        Ok(item_id)
+    }
+}
+
+/// Come up with a name for a set of alternatives, based on the names of its
+/// members. We look for words that all of the names share at the end ("Oak
+/// Wood Planks", "Birch Wood Planks" -> "any Wood Planks") and then at the
+/// start, falling back to listing the first member.
+fn group_name(names: &[&str]) -> String {
+    let words: Vec<Vec<&str>> = names.iter().map(|x| x.split_whitespace().collect()).collect();
+    let shortest = words.iter().map(|x| x.len()).min().unwrap_or(0);
+
+    let common_suffix = (0..shortest)
+        .take_while(|&i| {
+            let word = words[0][words[0].len() - 1 - i];
+            words.iter().all(|x| x[x.len() - 1 - i] == word)
+        }).count();
+    if common_suffix > 0 {
+        let first = &words[0];
+        return format!("any {}", first[first.len() - common_suffix..].join(" "));
+    }
+
+    let common_prefix = (0..shortest)
+        .take_while(|&i| words.iter().all(|x| x[i] == words[0][i]))
+        .count();
+    if common_prefix > 0 {
+        return format!("any {}", words[0][..common_prefix].join(" "));
+    }
+
+    match names.len() - 1 {
+        1 => format!("{} or 1 other", names[0]),
+        others => format!("{} or {} others", names[0], others),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(db: &mut RecipeDatabase, name: &str) -> RecipeComponent {
+        let name = db.get_or_intern(name);
+        db.associate_type(name, ItemType::Item);
+        RecipeComponent::ItemStack { count: 1, name }
+    }
+
+    #[test]
+    fn hash_is_taken_before_consumption_changes_the_recipe() {
+        let mut db = RecipeDatabase::new();
+        let machine = db.get_or_intern("minecraft.crafting");
+        db.add_machine(machine, "Crafting".to_owned(), "crafting.png".to_owned());
+
+        // The bucket is handed back, so marking consumption removes it from
+        // the outputs
+        let mut recipe = Recipe::new(machine);
+        for name in &["minecraft:bucket:0", "minecraft:sugar:0"] {
+            let mut slot = CraftingSlot::new();
+            slot.allowed_elements.push(item(&mut db, name));
+            recipe.inputs.push(slot);
+        }
+        recipe.outputs.push(item(&mut db, "minecraft:cake:0"));
+        recipe.outputs.push(item(&mut db, "minecraft:bucket:0"));
+        let hash = db.content_hash(&recipe);
+
+        assert!(db.add_recipe(recipe));
+        db.mark_unconsumed_inputs(&[]);
+        assert_eq!(db.recipes[0].outputs.len(), 1);
+        assert_eq!(db.recipes[0].content_hash, hash);
+    }
+
+    #[test]
+    fn copies_in_sibling_categories_are_merged() {
+        let mut db = RecipeDatabase::new();
+        let furnace = db.get_or_intern("minecraft.smelting");
+        let fuel = db.get_or_intern("minecraft.fuel");
+        let catalyst = db.get_or_intern("minecraft:furnace:0");
+        for machine in &[furnace, fuel] {
+            db.add_machine(*machine, "Furnace".to_owned(), "furnace.png".to_owned());
+            db.add_machine_catalyst(*machine, catalyst);
+        }
+
+        let smelt = |db: &mut RecipeDatabase, machine| {
+            let mut recipe = Recipe::new(machine);
+            let mut slot = CraftingSlot::new();
+            slot.allowed_elements.push(item(db, "minecraft:iron_ore:0"));
+            recipe.inputs.push(slot);
+            recipe.outputs.push(item(db, "minecraft:iron_ingot:0"));
+            recipe
+        };
+
+        let first = smelt(&mut db, furnace);
+        assert!(db.add_recipe(first));
+        // Both categories are shown for the furnace, so they're one machine
+        let other_category = smelt(&mut db, fuel);
+        assert!(!db.add_recipe(other_category));
+
+        assert_eq!(db.num_recipes(), 1);
+        assert_eq!(db.num_duplicates(), 1);
+        let duplicates = db.duplicates();
+        assert_eq!(duplicates[0].machine, "minecraft.smelting");
+        assert_eq!(duplicates[0].copies_from, vec!["minecraft.fuel"]);
+    }
+
+    #[test]
+    fn groups_are_named_after_members_without_tooltips_too() {
+        let mut db = RecipeDatabase::new();
+        let machine = db.get_or_intern("minecraft.crafting");
+        db.add_machine(machine, "Crafting".to_owned(), "crafting.png".to_owned());
+        // Nothing gives the rod a name, so it goes by its ID
+        let rod = item(&mut db, "mod:rod:0");
+        let stick = item(&mut db, "minecraft:stick:0");
+        db.associate_name(stick.get_name(), "Stick".to_owned());
+
+        for output in &["minecraft:torch:0", "minecraft:ladder:0"] {
+            let mut recipe = Recipe::new(machine);
+            let mut slot = CraftingSlot::new();
+            slot.allowed_elements = vec![stick.clone(), rod.clone()];
+            recipe.inputs.push(slot);
+            recipe.outputs.push(item(&mut db, output));
+            assert!(db.add_recipe(recipe));
+        }
+
+        db.detect_ingredient_groups();
+        assert_eq!(db.ingredient_groups.len(), 1);
+        assert_eq!(db.ingredient_groups[0].name, "mod:rod:0 or 1 other");
+        assert!(db.recipes.iter().all(|x| x.inputs[0].group == Some(0)));
+    }
+
+    const CRAFTING: &str = "minecraft.crafting";
+    const SMELTING: &str = "minecraft.smelting";
+    const ORE: &str = "minecraft:iron_ore:0";
+    const INGOT: &str = "minecraft:iron_ingot:0";
+    const BLOCK: &str = "minecraft:iron_block:0";
+    const NUGGET: &str = "minecraft:iron_nugget:0";
+    const STICK: &str = "minecraft:stick:0";
+    const TORCH: &str = "minecraft:torch:0";
+
+    fn stack(item: &str) -> ManualStack {
+        ManualStack {
+            item: item.to_owned(),
+            quantity: 1,
+            fluid: false,
+        }
+    }
+
+    fn manual(machine: &str, input: &str, output: &str) -> ManualRecipe {
+        ManualRecipe {
+            machine: machine.to_owned(),
+            inputs: vec![vec![stack(input)]],
+            outputs: vec![stack(output)],
+        }
+    }
+
+    /// Crafting and smelting, with iron made both ways and a torch crafted
+    fn override_db() -> RecipeDatabase {
+        let mut db = RecipeDatabase::new();
+        for (uid, name) in &[(CRAFTING, "Crafting"), (SMELTING, "Smelting")] {
+            let machine = db.get_or_intern(uid);
+            db.add_machine(machine, name.to_string(), String::new());
+        }
+        db.apply_overrides(&[
+            RecipeOverride::Add(manual(SMELTING, ORE, INGOT)),
+            RecipeOverride::Add(manual(CRAFTING, BLOCK, INGOT)),
+            RecipeOverride::Add(manual(CRAFTING, STICK, TORCH)),
+        ]);
+        assert_eq!(db.num_recipes(), 3);
+        db
+    }
+
+    /// The inputs of every recipe, in order
+    fn recipe_inputs(db: &RecipeDatabase) -> Vec<&str> {
+        db.recipes
+            .iter()
+            .map(|x| db.interner.resolve(x.inputs[0].allowed_elements[0].get_name()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn overrides_remove_recipes_everywhere() {
+        let mut db = override_db();
+        let hashes: Vec<String> = db.recipes[..2].iter().map(|x| x.content_hash.clone()).collect();
+        db.apply_overrides(&[RecipeOverride::Remove {
+            output: INGOT.to_owned(),
+            machine: None,
+        }]);
+        assert_eq!(recipe_inputs(&db), vec![STICK]);
+        // Copies left in the database by earlier imports get hidden
+        assert_eq!(db.removed, hashes);
+    }
+
+    #[test]
+    fn overrides_remove_recipes_on_one_machine() {
+        let mut db = override_db();
+        let hash = db.recipes[1].content_hash.clone();
+        db.apply_overrides(&[RecipeOverride::Remove {
+            output: INGOT.to_owned(),
+            machine: Some(CRAFTING.to_owned()),
+        }]);
+        assert_eq!(recipe_inputs(&db), vec![ORE, STICK]);
+        assert_eq!(db.removed, vec![hash]);
+    }
+
+    #[test]
+    fn overrides_replace_recipes() {
+        let mut db = override_db();
+        let hash = db.recipes[1].content_hash.clone();
+        db.apply_overrides(&[RecipeOverride::Replace(manual(CRAFTING, NUGGET, INGOT))]);
+        assert_eq!(recipe_inputs(&db), vec![ORE, STICK, NUGGET]);
+        assert_eq!(db.removed, vec![hash]);
+    }
+
+    #[test]
+    fn copies_still_merge_after_removals() {
+        let mut db = override_db();
+        db.apply_overrides(&[
+            RecipeOverride::Remove {
+                output: INGOT.to_owned(),
+                machine: Some(SMELTING.to_owned()),
+            },
+            // The torch recipe moved down, and its copy still finds it
+            RecipeOverride::Add(manual(CRAFTING, STICK, TORCH)),
+            // The removed recipe is gone, so adding it back isn't a copy
+            RecipeOverride::Add(manual(SMELTING, ORE, INGOT)),
+        ]);
+        assert_eq!(recipe_inputs(&db), vec![BLOCK, STICK, ORE]);
+        assert_eq!(db.num_duplicates(), 1);
+        assert_eq!(db.duplicates()[0].outputs, vec![TORCH]);
+    }
+
+    #[test]
+    fn group_name_uses_common_suffix() {
+        assert_eq!(group_name(&["Oak Wood Planks", "Birch Wood Planks"]), "any Wood Planks");
+        assert_eq!(group_name(&["Iron Ingot", "Copper Ingot", "Tin Ingot"]), "any Ingot");
+    }
+
+    #[test]
+    fn group_name_falls_back_to_common_prefix() {
+        assert_eq!(group_name(&["Copper Ore", "Copper Dust"]), "any Copper");
+    }
+
+    #[test]
+    fn group_name_falls_back_to_first_name() {
+        assert_eq!(group_name(&["Stick", "Bone", "Blaze Rod"]), "Stick or 2 others");
+        assert_eq!(group_name(&["Stick", "Bone"]), "Stick or 1 other");
     }
 }
@@ -0,0 +1,55 @@
+//! A summary of what an import did, for checking that an export was read the
+//! way we expected.
+
+use std::fmt::Write;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ImportReport {
+    /// How many recipe files were read
+    pub files: usize,
+    /// Files that couldn't be read, along with why
+    pub failed_files: Vec<String>,
+    /// How many distinct recipes were kept
+    pub recipes: usize,
+    /// How many copies of recipes were dropped
+    pub duplicates_merged: usize,
+    pub duplicates: Vec<DuplicateRecipe>,
+}
+
+/// A recipe that showed up more than once in the export
+#[derive(Serialize, Debug, Clone)]
+pub struct DuplicateRecipe {
+    /// The category of the copy that was kept
+    pub machine: String,
+    pub outputs: Vec<String>,
+    /// The category of each copy that was dropped
+    pub copies_from: Vec<String>,
+}
+
+impl ImportReport {
+    /// A summary for reading in a terminal
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "Files read: {}", self.files).unwrap();
+        for failure in &self.failed_files {
+            writeln!(out, "  failed: {}", failure).unwrap();
+        }
+        writeln!(out, "Recipes: {}", self.recipes).unwrap();
+        writeln!(
+            out,
+            "Duplicate copies merged: {} (of {} recipes)",
+            self.duplicates_merged,
+            self.duplicates.len()
+        ).unwrap();
+        for duplicate in &self.duplicates {
+            writeln!(
+                out,
+                "  {} in {}, also in {}",
+                duplicate.outputs.join(", "),
+                duplicate.machine,
+                duplicate.copies_from.join(", ")
+            ).unwrap();
+        }
+        out
+    }
+}
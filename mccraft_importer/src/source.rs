@@ -0,0 +1,111 @@
+//! Where an export is read from.
+
+use archive;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use types::MCCraftError;
+
+#[derive(Debug, Clone)]
+pub enum ExportSource {
+    /// The folder jeiexporter wrote, which holds `exports`
+    Folder(PathBuf),
+    /// A zip or .tar.gz of that folder
+    Archive(PathBuf),
+    /// Individual files from `exports`
+    Files(Vec<PathBuf>),
+}
+
+impl ExportSource {
+    /// Work out what `paths` hold. A single path is a folder or an archive,
+    /// unless it's a JSON file, and anything else is a list of files.
+    pub fn from_paths(mut paths: Vec<PathBuf>) -> ExportSource {
+        if paths.len() == 1 && !is_json(&paths[0]) {
+            let path = paths.pop().unwrap();
+            if path.is_dir() {
+                return ExportSource::Folder(path);
+            }
+            return ExportSource::Archive(path);
+        }
+        ExportSource::Files(paths)
+    }
+
+    /// Call `f` with the name and contents of every file in the export
+    pub fn for_each_file(
+        &self,
+        mut f: impl FnMut(&str, &mut dyn Read) -> Result<(), MCCraftError>,
+    ) -> Result<(), MCCraftError> {
+        match *self {
+            ExportSource::Folder(ref base_folder) => {
+                for file in base_folder.join("exports").read_dir()? {
+                    let path = file?.path();
+                    if path.is_file() {
+                        read_file(&path, &mut f)?;
+                    }
+                }
+                Ok(())
+            }
+            ExportSource::Archive(ref path) => archive::read_exports(path, f),
+            ExportSource::Files(ref paths) => {
+                for path in paths {
+                    read_file(path, &mut f)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether `path` looks like a JSON file
+pub fn is_json(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .extension()
+        .map(|x| x.eq_ignore_ascii_case("json"))
+        .unwrap_or(false)
+}
+
+fn read_file(
+    path: &Path,
+    f: &mut impl FnMut(&str, &mut dyn Read) -> Result<(), MCCraftError>,
+) -> Result<(), MCCraftError> {
+    let name = path
+        .file_name()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    f(&name, &mut File::open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_paths_tells_folders_from_archives() {
+        let folder = std::env::temp_dir();
+        match ExportSource::from_paths(vec![folder.clone()]) {
+            ExportSource::Folder(path) => assert_eq!(path, folder),
+            other => panic!("expected a folder, got {:?}", other),
+        }
+
+        let archive = PathBuf::from("pack.zip");
+        match ExportSource::from_paths(vec![archive.clone()]) {
+            ExportSource::Archive(path) => assert_eq!(path, archive),
+            other => panic!("expected an archive, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn from_paths_takes_json_files_as_a_list() {
+        let single = vec![PathBuf::from("exports/minecraft.crafting.json")];
+        match ExportSource::from_paths(single.clone()) {
+            ExportSource::Files(paths) => assert_eq!(paths, single),
+            other => panic!("expected files, got {:?}", other),
+        }
+
+        let several = vec![PathBuf::from("a.json"), PathBuf::from("b.json")];
+        match ExportSource::from_paths(several.clone()) {
+            ExportSource::Files(paths) => assert_eq!(paths, several),
+            other => panic!("expected files, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Counts of what's in a pack, for a quick look at an export or the database.
+
+use mccraft_core::diff::Snapshot;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Serialize, Debug)]
+pub struct PackStats {
+    pub items: usize,
+    pub machines: usize,
+    pub recipes: usize,
+    /// Every machine, busiest first
+    pub by_machine: Vec<MachineStats>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MachineStats {
+    /// The category UID of the machine
+    pub machine: String,
+    pub name: String,
+    pub recipes: usize,
+}
+
+impl PackStats {
+    pub fn compute(snapshot: &Snapshot) -> PackStats {
+        let mut counts: BTreeMap<&str, usize> = snapshot
+            .machines
+            .keys()
+            .map(|x| (x.as_str(), 0))
+            .collect();
+        for recipe in &snapshot.recipes {
+            *counts.entry(&recipe.machine).or_insert(0) += 1;
+        }
+
+        let mut by_machine: Vec<MachineStats> = counts
+            .into_iter()
+            .map(|(machine, recipes)| MachineStats {
+                machine: machine.to_owned(),
+                name: snapshot.machines.get(machine).cloned().unwrap_or_default(),
+                recipes,
+            }).collect();
+        by_machine.sort_by(|a, b| b.recipes.cmp(&a.recipes));
+
+        PackStats {
+            items: snapshot.items.len(),
+            machines: snapshot.machines.len(),
+            recipes: snapshot.recipes.len(),
+            by_machine,
+        }
+    }
+
+    /// A summary for reading in a terminal
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "Items: {}", self.items).unwrap();
+        writeln!(out, "Machines: {}", self.machines).unwrap();
+        writeln!(out, "Recipes: {}", self.recipes).unwrap();
+        for machine in &self.by_machine {
+            writeln!(
+                out,
+                "  {:>7}  {} ({})",
+                machine.recipes, machine.name, machine.machine
+            ).unwrap();
+        }
+        out
+    }
+}
@@ -1,15 +1,17 @@
+use diesel::result::Error as DieselError;
 use fxhash::FxBuildHasher;
 use mccraft_core::json::recipe;
 use mccraft_core::sql::ItemType;
 use serde_json::error::Error as JSONError;
 use std::io;
 use string_interner::{self, Sym};
+use zip::result::ZipError;
 use ::recipe_db::RecipeDatabase;
 
 pub type StringInterner = string_interner::StringInterner<Sym, FxBuildHasher>;
 
 /// A crafting ingredient
-#[derive(PartialEq, Eq, Debug, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub enum RecipeComponent {
     ItemStack { count: u32, name: Sym },
     Fluid { amount: u32, name: Sym },
@@ -55,22 +57,45 @@ impl RecipeComponent {
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct CraftingSlot {
     pub allowed_elements: Vec<RecipeComponent>,
+    /// Index of the ingredient group that covers exactly the allowed elements,
+    /// if there is one
+    pub group: Option<usize>,
+    /// Whether the recipe uses up the item in this slot
+    pub consumed: bool,
+    /// Where the slot is drawn in the JEI layout
+    pub position: Option<(i32, i32)>,
 }
 
 impl CraftingSlot {
     pub fn new() -> CraftingSlot {
         CraftingSlot {
             allowed_elements: Vec::new(),
+            group: None,
+            consumed: true,
+            position: None,
         }
     }
 }
 
+/// A set of alternatives that shows up in many crafting slots, like "any Wood
+/// Planks"
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct IngredientGroup {
+    pub name: String,
+    /// The alternatives, sorted so that identical sets compare equal
+    pub members: Vec<RecipeComponent>,
+}
+
 /// An individual recipe
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Recipe {
     pub machine: Sym,
     pub inputs: Vec<CraftingSlot>,
     pub outputs: Vec<RecipeComponent>,
+    /// Stable hash of the recipe as it was exported. Filled in by
+    /// `RecipeDatabase::add_recipe`, before overrides get a chance to change
+    /// the recipe, so the same export always gives the same hash.
+    pub content_hash: String,
 }
 
 impl Recipe {
@@ -79,15 +104,38 @@ impl Recipe {
             machine,
             inputs: Vec::new(),
             outputs: Vec::new(),
+            content_hash: String::new(),
         }
     }
 }
 
+/// The machine a recipe runs in, for deciding whether two recipes are copies
+/// of each other. Categories that share their catalysts are the same machine
+/// shown in different ways.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum MachineKey {
+    Category(Sym),
+    Catalysts(Vec<Sym>),
+}
+
+/// A recipe with the order of its slots, alternatives and outputs sorted away,
+/// so copies of the same recipe compare equal
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct NormalizedRecipe {
+    pub machine: MachineKey,
+    pub inputs: Vec<Vec<RecipeComponent>>,
+    pub outputs: Vec<RecipeComponent>,
+}
+
 /// Generic error type for passing around inside
 #[derive(Debug)]
 pub enum MCCraftError {
     IOError(io::Error),
     DeserializeError(JSONError),
+    ArchiveError(ZipError),
+    /// An uploaded archive that isn't laid out like a jeiexporter folder
+    BadExport(String),
+    DatabaseError(DieselError),
 }
 
 impl From<io::Error> for MCCraftError {
@@ -101,3 +149,15 @@ impl From<JSONError> for MCCraftError {
         MCCraftError::DeserializeError(o)
     }
 }
+
+impl From<ZipError> for MCCraftError {
+    fn from(o: ZipError) -> Self {
+        MCCraftError::ArchiveError(o)
+    }
+}
+
+impl From<DieselError> for MCCraftError {
+    fn from(o: DieselError) -> Self {
+        MCCraftError::DatabaseError(o)
+    }
+}
@@ -1,13 +1,13 @@
 use super::DbExecutor;
 use actix::prelude::*;
 use diesel::prelude::*;
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use mccraft_core::schema::mccraft as schema;
 use mccraft_core::sql;
 use mccraft_core::web::{self, InputSlot, ItemSpec};
 
 /// Retrieve the entire recipe.
-pub struct Recipe(pub i32);
+pub struct Recipe(pub web::RecipeRef);
 
 impl Message for Recipe {
     type Result = QueryResult<web::Recipe>;
@@ -17,25 +17,157 @@ impl Handler<Recipe> for DbExecutor {
     type Result = <Recipe as Message>::Result;
 
     fn handle(&mut self, msg: Recipe, _: &mut Self::Context) -> Self::Result {
-        use self::schema::{crafting_components, input_slots, items, outputs};
+        let recipe_id = self.resolve_recipe_refs(&[msg.0])?[0]
+            .ok_or(diesel::result::Error::NotFound)?;
+        self.load_recipe(recipe_id)
+    }
+}
+
+/// Retrieve several complete recipes at once. Recipes that don't exist are
+/// left out of the result.
+pub struct Recipes(pub Vec<web::RecipeRef>);
+
+impl Message for Recipes {
+    type Result = QueryResult<Vec<web::Recipe>>;
+}
+
+impl Handler<Recipes> for DbExecutor {
+    type Result = <Recipes as Message>::Result;
+
+    fn handle(&mut self, msg: Recipes, _: &mut Self::Context) -> Self::Result {
+        let ids: Vec<i32> = self
+            .resolve_recipe_refs(&msg.0)?
+            .into_iter()
+            .filter_map(|x| x)
+            .collect();
+        self.load_recipes(&ids)
+    }
+}
+
+/// The columns needed to build an ItemSpec, along with the ID of the recipe or
+/// slot it belongs to.
+type ItemSpecRow = (i32, i32, String, String, sql::ItemType, i32);
+
+fn item_spec(row: ItemSpecRow) -> (i32, ItemSpec) {
+    (
+        row.0,
+        ItemSpec {
+            item_id: row.1,
+            item_name: row.2,
+            minecraft_id: row.3,
+            ty: row.4,
+            quantity: row.5,
+        },
+    )
+}
+
+impl DbExecutor {
+    /// Turn recipe references in to recipe IDs. The result has one entry for
+    /// each reference, which is `None` if there is no such recipe.
+    pub(crate) fn resolve_recipe_refs(
+        &self,
+        refs: &[web::RecipeRef],
+    ) -> QueryResult<Vec<Option<i32>>> {
+        use self::schema::recipes;
+
+        let mut ids = Vec::new();
+        let mut hashes = Vec::new();
+        for r in refs {
+            match r {
+                web::RecipeRef::Id(id) => ids.push(*id),
+                web::RecipeRef::Hash(hash) => hashes.push(hash.as_str()),
+            }
+        }
+
+        let mut found: FxHashMap<web::RecipeRef, i32> = FxHashMap::default();
+        for id in recipes::table
+            .filter(recipes::id.eq_any(ids))
+            .filter(recipes::hidden.eq(false))
+            .select(recipes::id)
+            .load::<i32>(&self.0)?
+        {
+            found.insert(web::RecipeRef::Id(id), id);
+        }
+        for (hash, id) in recipes::table
+            .filter(recipes::content_hash.eq_any(hashes))
+            .filter(recipes::hidden.eq(false))
+            .select((recipes::content_hash, recipes::id))
+            .load::<(Option<String>, i32)>(&self.0)?
+        {
+            if let Some(hash) = hash {
+                found.insert(web::RecipeRef::Hash(hash), id);
+            }
+        }
+
+        Ok(refs.iter().map(|r| found.get(r).cloned()).collect())
+    }
+
+    /// Load all the inputs and outputs of a recipe
+    pub(crate) fn load_recipe(&self, recipe_id: i32) -> QueryResult<web::Recipe> {
+        self.load_recipes(&[recipe_id])?
+            .pop()
+            .ok_or(diesel::result::Error::NotFound)
+    }
+
+    /// Load all the inputs and outputs of a set of recipes. This takes the
+    /// same number of queries no matter how many recipes are requested. The
+    /// recipes are returned in the order they were asked for.
+    pub(crate) fn load_recipes(&self, recipe_ids: &[i32]) -> QueryResult<Vec<web::Recipe>> {
+        use self::schema::{
+            crafting_components, ingredient_group_members, ingredient_groups, input_slots, items,
+            outputs, recipes,
+        };
+
+        let recipe_rows: FxHashMap<i32, sql::Recipe> = recipes::table
+            .filter(recipes::id.eq_any(recipe_ids))
+            .filter(recipes::hidden.eq(false))
+            .load::<sql::Recipe>(&self.0)?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect();
+        let machine_ids: Vec<i32> = recipe_rows
+            .values()
+            .map(|x| x.machine)
+            .collect::<FxHashSet<_>>()
+            .into_iter()
+            .collect();
+        let machines = self.load_machines(&machine_ids)?;
 
         // Get all the data we need about the outputs.
-        let outputs: Vec<ItemSpec> = outputs::table
+        let mut outputs_by_recipe: FxHashMap<i32, Vec<ItemSpec>> = Default::default();
+        for (recipe, spec) in outputs::table
             .inner_join(items::table)
-            .filter(outputs::recipe.eq(msg.0))
+            .filter(outputs::recipe.eq_any(recipe_ids))
+            .order_by(outputs::id)
             .select((
+                outputs::recipe,
                 items::id,
                 items::human_name,
                 items::minecraft_id,
                 items::ty,
                 outputs::quantity,
-            )).load::<ItemSpec>(&self.0)?;
+            )).load::<ItemSpecRow>(&self.0)?
+            .into_iter()
+            .map(item_spec)
+        {
+            outputs_by_recipe
+                .entry(recipe)
+                .or_insert_with(Vec::new)
+                .push(spec);
+        }
+
+        // Every input slot, and the recipe it belongs to
+        let slots: Vec<(i32, i32)> = input_slots::table
+            .filter(input_slots::for_recipe.eq_any(recipe_ids))
+            .order_by(input_slots::id)
+            .select((input_slots::id, input_slots::for_recipe))
+            .load::<(i32, i32)>(&self.0)?;
 
         // Get all the data about all of the inputs
-        let inputs: Vec<_> = crafting_components::table
+        let inputs: Vec<(i32, ItemSpec)> = crafting_components::table
             .inner_join(items::table)
             .inner_join(input_slots::table)
-            .filter(input_slots::for_recipe.eq(msg.0))
+            .filter(input_slots::for_recipe.eq_any(recipe_ids))
             .select((
                 input_slots::id,
                 items::id,
@@ -43,37 +175,114 @@ impl Handler<Recipe> for DbExecutor {
                 items::minecraft_id,
                 items::ty,
                 crafting_components::quantity,
-            )).load::<(i32, i32, String, String, sql::ItemType, i32)>(&self.0)?
+            )).load::<ItemSpecRow>(&self.0)?
             .into_iter()
-            .map(|v| (v.0, (v.1, v.2, v.3, v.4, v.5)))
+            .map(item_spec)
             .collect();
 
-        let mut inputs_flattened: FxHashMap<i32, _> = Default::default();
-        for (k, v) in inputs.into_iter() {
-            inputs_flattened
-                .entry(k)
-                .or_insert_with(Vec::new)
-                .push(ItemSpec {
-                    item_id: v.0,
-                    item_name: v.1,
-                    minecraft_id: v.2,
-                    ty: v.3,
-                    quantity: v.4,
-                });
-        }
+        // Slots that use an ingredient group get their items from the group
+        let grouped_inputs: Vec<(i32, ItemSpec)> = ingredient_group_members::table
+            .inner_join(items::table)
+            .inner_join(
+                input_slots::table.on(input_slots::ingredient_group
+                    .eq(ingredient_group_members::ingredient_group.nullable())),
+            ).filter(input_slots::for_recipe.eq_any(recipe_ids))
+            .select((
+                input_slots::id,
+                items::id,
+                items::human_name,
+                items::minecraft_id,
+                items::ty,
+                ingredient_group_members::quantity,
+            )).load::<ItemSpecRow>(&self.0)?
+            .into_iter()
+            .map(item_spec)
+            .collect();
+
+        let mut groups: FxHashMap<i32, web::IngredientGroup> = input_slots::table
+            .inner_join(ingredient_groups::table)
+            .filter(input_slots::for_recipe.eq_any(recipe_ids))
+            .select((
+                input_slots::id,
+                ingredient_groups::id,
+                ingredient_groups::human_name,
+            )).load::<(i32, i32, String)>(&self.0)?
+            .into_iter()
+            .map(|(slot, group_id, group_name)| {
+                (
+                    slot,
+                    web::IngredientGroup {
+                        group_id,
+                        group_name,
+                    },
+                )
+            }).collect();
 
-        let inputs: Vec<InputSlot> = inputs_flattened
+        // Slots holding tools or catalysts
+        let unconsumed: FxHashSet<i32> = crafting_components::table
+            .inner_join(input_slots::table)
+            .filter(input_slots::for_recipe.eq_any(recipe_ids))
+            .filter(crafting_components::consumed.eq(false))
+            .select(input_slots::id)
+            .load::<i32>(&self.0)?
             .into_iter()
-            .map(|(_, v)| InputSlot { items: v })
             .collect();
 
-        Ok(web::Recipe {
-            input_slots: inputs,
-            outputs,
-        })
+        let mut items_by_slot: FxHashMap<i32, Vec<ItemSpec>> = Default::default();
+        for (k, v) in inputs.into_iter().chain(grouped_inputs.into_iter()) {
+            items_by_slot.entry(k).or_insert_with(Vec::new).push(v);
+        }
+
+        let mut inputs_by_recipe: FxHashMap<i32, Vec<InputSlot>> = Default::default();
+        for (slot, recipe) in slots {
+            let items = match items_by_slot.remove(&slot) {
+                Some(items) => items,
+                None => continue,
+            };
+            inputs_by_recipe
+                .entry(recipe)
+                .or_insert_with(Vec::new)
+                .push(InputSlot {
+                    items,
+                    group: groups.remove(&slot),
+                    consumed: !unconsumed.contains(&slot),
+                });
+        }
+
+        Ok(assemble_recipes(
+            recipe_ids,
+            &recipe_rows,
+            &machines,
+            &inputs_by_recipe,
+            &outputs_by_recipe,
+        ))
     }
 }
 
+/// Put the pieces loaded by `load_recipes` together, in the order the recipes
+/// were asked for. An ID that was asked for twice comes back twice.
+fn assemble_recipes(
+    recipe_ids: &[i32],
+    recipe_rows: &FxHashMap<i32, sql::Recipe>,
+    machines: &FxHashMap<i32, web::Machine>,
+    inputs_by_recipe: &FxHashMap<i32, Vec<InputSlot>>,
+    outputs_by_recipe: &FxHashMap<i32, Vec<ItemSpec>>,
+) -> Vec<web::Recipe> {
+    recipe_ids
+        .iter()
+        .filter_map(|recipe_id| {
+            let row = recipe_rows.get(recipe_id)?;
+            let machine = machines.get(&row.machine)?;
+            Some(web::Recipe {
+                recipe_id: *recipe_id,
+                recipe_hash: row.content_hash.clone(),
+                machine: machine.clone(),
+                input_slots: inputs_by_recipe.get(recipe_id).cloned().unwrap_or_default(),
+                outputs: outputs_by_recipe.get(recipe_id).cloned().unwrap_or_default(),
+            })
+        }).collect()
+}
+
 /// Get information about a specific item.
 pub struct Item(pub i32);
 
@@ -90,3 +299,161 @@ impl Handler<Item> for DbExecutor {
         items.find(msg.0).first::<sql::Item>(&self.0)
     }
 }
+
+/// Get information about an item by its Minecraft string ID.
+pub struct ItemByMinecraftId(pub String);
+
+impl Message for ItemByMinecraftId {
+    type Result = QueryResult<Option<sql::Item>>;
+}
+
+impl Handler<ItemByMinecraftId> for DbExecutor {
+    type Result = <ItemByMinecraftId as Message>::Result;
+
+    fn handle(&mut self, msg: ItemByMinecraftId, _: &mut Self::Context) -> Self::Result {
+        use self::schema::items::dsl::*;
+
+        items
+            .filter(minecraft_id.eq(msg.0))
+            .first::<sql::Item>(&self.0)
+            .optional()
+    }
+}
+
+/// Look up several items at once, by either kind of ID. There is one result
+/// for every requested item, in the same order, even if the item doesn't
+/// exist.
+pub struct LookupItems(pub Vec<web::ItemRef>);
+
+impl Message for LookupItems {
+    type Result = QueryResult<Vec<web::ItemLookup>>;
+}
+
+impl Handler<LookupItems> for DbExecutor {
+    type Result = <LookupItems as Message>::Result;
+
+    fn handle(&mut self, msg: LookupItems, _: &mut Self::Context) -> Self::Result {
+        use self::schema::items;
+
+        let mut ids = Vec::new();
+        let mut mcids = Vec::new();
+        for item in &msg.0 {
+            match item {
+                web::ItemRef::Id(id) => ids.push(*id),
+                web::ItemRef::MinecraftId(mcid) => mcids.push(mcid.as_str()),
+            }
+        }
+
+        let mut found: FxHashMap<web::ItemRef, sql::Item> = FxHashMap::default();
+        for item in items::table
+            .filter(items::id.eq_any(ids))
+            .load::<sql::Item>(&self.0)?
+        {
+            found.insert(web::ItemRef::Id(item.id), item);
+        }
+        for item in items::table
+            .filter(items::minecraft_id.eq_any(mcids))
+            .load::<sql::Item>(&self.0)?
+        {
+            found.insert(web::ItemRef::MinecraftId(item.minecraft_id.clone()), item);
+        }
+
+        Ok(msg
+            .0
+            .into_iter()
+            .map(|requested| {
+                // The same item may be asked for more than once
+                let item = found.get(&requested).cloned();
+                web::ItemLookup { requested, item }
+            }).collect())
+    }
+}
+
+/// Get all the variants (different metadata or NBT) of an item, including the
+/// item itself.
+pub struct ItemVariants(pub i32);
+
+impl Message for ItemVariants {
+    type Result = QueryResult<Vec<sql::Item>>;
+}
+
+impl Handler<ItemVariants> for DbExecutor {
+    type Result = <ItemVariants as Message>::Result;
+
+    fn handle(&mut self, msg: ItemVariants, _: &mut Self::Context) -> Self::Result {
+        use self::schema::items::dsl::*;
+
+        let base: String = items.find(msg.0).select(base_id).first(&self.0)?;
+
+        items
+            .filter(base_id.eq(base))
+            .order_by((meta, id))
+            .load::<sql::Item>(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mccraft_core::sql::ItemType;
+
+    fn spec(item_id: i32) -> ItemSpec {
+        ItemSpec {
+            item_id,
+            item_name: format!("Item {}", item_id),
+            minecraft_id: format!("test:item_{}:0", item_id),
+            ty: ItemType::Item,
+            quantity: 1,
+        }
+    }
+
+    #[test]
+    fn repeated_ids_get_complete_recipes() {
+        let mut recipe_rows = FxHashMap::default();
+        recipe_rows.insert(
+            7,
+            sql::Recipe {
+                id: 7,
+                machine: 1,
+                content_hash: Some("abc".to_owned()),
+                hidden: false,
+            },
+        );
+        let mut machines = FxHashMap::default();
+        machines.insert(
+            1,
+            web::Machine {
+                machine_id: 1,
+                machine_name: "Crafting".to_owned(),
+                minecraft_id: "minecraft.crafting".to_owned(),
+                bg_texture: String::new(),
+                catalysts: Vec::new(),
+            },
+        );
+        let mut inputs_by_recipe = FxHashMap::default();
+        inputs_by_recipe.insert(
+            7,
+            vec![InputSlot {
+                items: vec![spec(2)],
+                group: None,
+                consumed: true,
+            }],
+        );
+        let mut outputs_by_recipe = FxHashMap::default();
+        outputs_by_recipe.insert(7, vec![spec(3), spec(4)]);
+
+        let recipes = assemble_recipes(
+            &[7, 8, 7],
+            &recipe_rows,
+            &machines,
+            &inputs_by_recipe,
+            &outputs_by_recipe,
+        );
+        assert_eq!(recipes.len(), 2);
+        for recipe in &recipes {
+            assert_eq!(recipe.recipe_id, 7);
+            assert_eq!(recipe.input_slots.len(), 1);
+            assert_eq!(recipe.outputs.len(), 2);
+        }
+    }
+}
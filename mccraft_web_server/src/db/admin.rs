@@ -0,0 +1,204 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel;
+use diesel::prelude::*;
+use fxhash::FxHashMap;
+use mccraft_core::analysis::HealthReport;
+use mccraft_core::conflicts::ConflictReport;
+use mccraft_core::recipe_hash::{self, HashedStack};
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::sql::{self, ItemType};
+use mccraft_core::web;
+
+/// Look for problems in the recipe graph
+pub struct CheckHealth;
+
+impl Message for CheckHealth {
+    type Result = QueryResult<HealthReport>;
+}
+
+impl Handler<CheckHealth> for DbExecutor {
+    type Result = <CheckHealth as Message>::Result;
+
+    fn handle(&mut self, _: CheckHealth, _: &mut Self::Context) -> Self::Result {
+        HealthReport::compute(&self.0)
+    }
+}
+
+/// Look for recipes that take the same inputs
+pub struct FindConflicts {
+    /// JEI categories whose recipes are compared slot by slot
+    pub shaped_categories: Vec<String>,
+}
+
+impl Message for FindConflicts {
+    type Result = QueryResult<ConflictReport>;
+}
+
+impl Handler<FindConflicts> for DbExecutor {
+    type Result = <FindConflicts as Message>::Result;
+
+    fn handle(&mut self, msg: FindConflicts, _: &mut Self::Context) -> Self::Result {
+        ConflictReport::compute(&self.0, &msg.shaped_categories)
+    }
+}
+
+/// Add a recipe by hand. Every item and the machine have to be in the
+/// database already. If the recipe is already there it's made visible again.
+pub struct CreateRecipe(pub web::ManualRecipe);
+
+impl Message for CreateRecipe {
+    /// The inner error describes what's wrong with the request
+    type Result = QueryResult<Result<web::Recipe, String>>;
+}
+
+impl Handler<CreateRecipe> for DbExecutor {
+    type Result = <CreateRecipe as Message>::Result;
+
+    fn handle(&mut self, msg: CreateRecipe, _: &mut Self::Context) -> Self::Result {
+        use self::schema::{crafting_components, input_slots, items, machines, outputs, recipes};
+
+        let recipe = msg.0;
+        let machine = match machines::table
+            .filter(machines::minecraft_id.eq(&recipe.machine))
+            .select(machines::id)
+            .first::<i32>(&self.0)
+            .optional()?
+        {
+            Some(machine) => machine,
+            None => return Ok(Err(format!("No such machine {:?}", recipe.machine))),
+        };
+
+        let mcids: Vec<&str> = recipe
+            .inputs
+            .iter()
+            .flat_map(|x| x.iter())
+            .chain(recipe.outputs.iter())
+            .map(|x| x.item.as_str())
+            .collect();
+        let known: FxHashMap<String, (i32, ItemType)> = items::table
+            .filter(items::minecraft_id.eq_any(&mcids))
+            .select((items::minecraft_id, items::id, items::ty))
+            .load::<(String, i32, ItemType)>(&self.0)?
+            .into_iter()
+            .map(|(mcid, id, ty)| (mcid, (id, ty)))
+            .collect();
+        if let Some(missing) = mcids.iter().find(|x| !known.contains_key(**x)) {
+            return Ok(Err(format!("No such item {:?}", missing)));
+        }
+
+        fn hashed<'a>(
+            known: &FxHashMap<String, (i32, ItemType)>,
+            stack: &'a web::ManualStack,
+        ) -> HashedStack<'a> {
+            HashedStack {
+                fluid: match known[&stack.item].1 {
+                    ItemType::Fluid => true,
+                    _ => false,
+                },
+                name: &stack.item,
+                quantity: stack.quantity,
+            }
+        }
+        let hash = recipe_hash::content_hash(
+            &recipe.machine,
+            &recipe
+                .inputs
+                .iter()
+                .map(|x| x.iter().map(|stack| hashed(&known, stack)).collect())
+                .collect::<Vec<_>>(),
+            &recipe
+                .outputs
+                .iter()
+                .map(|stack| hashed(&known, stack))
+                .collect::<Vec<_>>(),
+        );
+
+        self.0.transaction(|| {
+            let existing = diesel::update(recipes::table.filter(recipes::content_hash.eq(&hash)))
+                .set(recipes::hidden.eq(false))
+                .returning(recipes::id)
+                .get_result::<i32>(&self.0)
+                .optional()?;
+            if let Some(id) = existing {
+                return self.load_recipe(id).map(Ok);
+            }
+
+            let id = diesel::insert_into(recipes::table)
+                .values(&sql::NewRecipe {
+                    machine,
+                    content_hash: &hash,
+                }).returning(recipes::id)
+                .get_result::<i32>(&self.0)?;
+
+            for alternatives in &recipe.inputs {
+                let slot = diesel::insert_into(input_slots::table)
+                    .values(&sql::NewInputSlot {
+                        for_recipe: id,
+                        ingredient_group: None,
+                        x: None,
+                        y: None,
+                    }).returning(input_slots::id)
+                    .get_result::<i32>(&self.0)?;
+                let components: Vec<_> = alternatives
+                    .iter()
+                    .map(|stack| sql::NewCraftingComponent {
+                        crafting_slot: slot,
+                        quantity: stack.quantity,
+                        item: known[&stack.item].0,
+                        consumed: true,
+                    }).collect();
+                diesel::insert_into(crafting_components::table)
+                    .values(&components)
+                    .execute(&self.0)?;
+            }
+
+            let new_outputs: Vec<_> = recipe
+                .outputs
+                .iter()
+                .map(|stack| sql::NewOutput {
+                    recipe: id,
+                    quantity: stack.quantity,
+                    item: known[&stack.item].0,
+                }).collect();
+            diesel::insert_into(outputs::table)
+                .values(&new_outputs)
+                .execute(&self.0)?;
+
+            self.load_recipe(id).map(Ok)
+        })
+    }
+}
+
+/// Hide a recipe from the web interface, or bring it back
+pub struct SetRecipeHidden {
+    pub recipe: web::RecipeRef,
+    pub hidden: bool,
+}
+
+impl Message for SetRecipeHidden {
+    /// Whether the recipe exists
+    type Result = QueryResult<bool>;
+}
+
+impl Handler<SetRecipeHidden> for DbExecutor {
+    type Result = <SetRecipeHidden as Message>::Result;
+
+    fn handle(&mut self, msg: SetRecipeHidden, _: &mut Self::Context) -> Self::Result {
+        use self::schema::recipes;
+
+        // Hidden recipes can't be found through resolve_recipe_refs, so look
+        // them up directly
+        let updated = match msg.recipe {
+            web::RecipeRef::Id(id) => diesel::update(recipes::table.find(id))
+                .set(recipes::hidden.eq(msg.hidden))
+                .execute(&self.0)?,
+            web::RecipeRef::Hash(hash) => {
+                diesel::update(recipes::table.filter(recipes::content_hash.eq(hash)))
+                    .set(recipes::hidden.eq(msg.hidden))
+                    .execute(&self.0)?
+            }
+        };
+        Ok(updated > 0)
+    }
+}
@@ -0,0 +1,203 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel::prelude::*;
+use fxhash::FxHashMap;
+use mccraft_core::bom::{self, ContainerEquivalence, FluidContainers, Process, ProcessInput};
+use mccraft_core::export::{Format, ShoppingList};
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::sql;
+use mccraft_core::web::{self, BomItem};
+use std::collections::{BTreeMap, HashMap};
+
+/// Compute a bill of materials
+pub struct ComputeBom(pub web::BomRequest);
+
+impl Message for ComputeBom {
+    type Result = QueryResult<web::Bom>;
+}
+
+impl Handler<ComputeBom> for DbExecutor {
+    type Result = <ComputeBom as Message>::Result;
+
+    fn handle(&mut self, msg: ComputeBom, _: &mut Self::Context) -> Self::Result {
+        Ok(self.compute_bom(&msg.0)?.0)
+    }
+}
+
+/// Compute a bill of materials, and render it in one of the export formats
+pub struct ExportBom {
+    pub request: web::BomRequest,
+    pub format: Format,
+}
+
+impl Message for ExportBom {
+    type Result = QueryResult<String>;
+}
+
+impl Handler<ExportBom> for DbExecutor {
+    type Result = <ExportBom as Message>::Result;
+
+    fn handle(&mut self, msg: ExportBom, _: &mut Self::Context) -> Self::Result {
+        self.export_bom("", &msg.request, msg.format)
+    }
+}
+
+/// Reduce a recipe to what the BOM calculation needs
+fn process_for(recipe: &web::Recipe) -> Process {
+    Process {
+        recipe_id: recipe.recipe_id,
+        inputs: recipe
+            .input_slots
+            .iter()
+            .map(|slot| ProcessInput {
+                alternatives: slot
+                    .items
+                    .iter()
+                    .map(|x| (x.item_id, x.quantity as i64))
+                    .collect(),
+                consumed: slot.consumed,
+            }).collect(),
+        outputs: recipe
+            .outputs
+            .iter()
+            .map(|x| (x.item_id, x.quantity as i64))
+            .collect(),
+    }
+}
+
+impl DbExecutor {
+    /// Compute a bill of materials. Also returns the recipe chosen for each
+    /// item, by item ID.
+    pub(crate) fn compute_bom(
+        &self,
+        request: &web::BomRequest,
+    ) -> QueryResult<(web::Bom, HashMap<i32, web::Recipe>)> {
+        let containers = self.load_fluid_containers()?;
+
+        let refs: Vec<web::RecipeRef> = request
+            .recipes
+            .iter()
+            .map(|x| x.recipe_id.clone())
+            .collect();
+        let recipe_ids = self
+            .resolve_recipe_refs(&refs)?
+            .into_iter()
+            .collect::<Option<Vec<i32>>>()
+            .ok_or(diesel::result::Error::NotFound)?;
+        let recipes: HashMap<i32, web::Recipe> = self
+            .load_recipes(&recipe_ids)?
+            .into_iter()
+            .map(|x| (x.recipe_id, x))
+            .collect();
+
+        let mut processes = HashMap::new();
+        let mut chosen = HashMap::new();
+        for (choice, recipe_id) in request.recipes.iter().zip(recipe_ids.iter()) {
+            let recipe = recipes
+                .get(recipe_id)
+                .ok_or(diesel::result::Error::NotFound)?;
+            chosen.insert(choice.item_id, recipe.clone());
+            let mut process = process_for(recipe);
+
+            if request.include_machines && !recipe.machine.catalysts.is_empty() {
+                // Any one of the machine's catalysts will do, and it only needs
+                // to be built once.
+                process.inputs.push(ProcessInput {
+                    alternatives: recipe
+                        .machine
+                        .catalysts
+                        .iter()
+                        .map(|x| (x.item_id, 1))
+                        .collect(),
+                    consumed: false,
+                });
+            }
+
+            processes.insert(choice.item_id, process);
+        }
+
+        let targets: Vec<(i32, i64)> = request
+            .targets
+            .iter()
+            .map(|x| (x.item_id, x.quantity as i64))
+            .collect();
+
+        let computed = bom::compute(&targets, &processes, &containers);
+
+        let bom = web::Bom {
+            raw: self.item_specs(&computed.raw)?,
+            crafts: computed
+                .crafts
+                .into_iter()
+                .map(|(recipe_id, crafts)| web::CraftCount { recipe_id, crafts })
+                .collect(),
+            leftovers: self.item_specs(&computed.leftovers)?,
+            tools: self.item_specs(&computed.tools)?,
+        };
+
+        Ok((bom, chosen))
+    }
+
+    /// Compute a bill of materials and render it as text
+    pub(crate) fn export_bom(
+        &self,
+        name: &str,
+        request: &web::BomRequest,
+        format: Format,
+    ) -> QueryResult<String> {
+        let (bom, chosen) = self.compute_bom(request)?;
+
+        let mut quantities = BTreeMap::new();
+        for target in &request.targets {
+            *quantities.entry(target.item_id).or_insert(0) += target.quantity as i64;
+        }
+        let targets = self.item_specs(&quantities)?;
+
+        Ok(ShoppingList {
+            name,
+            bom: &bom,
+            targets: &targets,
+            chosen: &chosen,
+        }.render(format)
+        .unwrap_or_default())
+    }
+
+    pub(crate) fn load_fluid_containers(&self) -> QueryResult<FluidContainers> {
+        use self::schema::fluid_containers;
+
+        Ok(FluidContainers::new(
+            fluid_containers::table
+                .select((
+                    fluid_containers::filled_item,
+                    fluid_containers::fluid,
+                    fluid_containers::amount,
+                    fluid_containers::empty_item,
+                )).load::<ContainerEquivalence>(&self.0)?,
+        ))
+    }
+
+    /// Attach item information to a list of quantities
+    fn item_specs(&self, quantities: &BTreeMap<i32, i64>) -> QueryResult<Vec<BomItem>> {
+        use self::schema::items;
+
+        let ids: Vec<i32> = quantities.keys().cloned().collect();
+        let known: FxHashMap<i32, sql::Item> = items::table
+            .filter(items::id.eq_any(ids))
+            .load::<sql::Item>(&self.0)?
+            .into_iter()
+            .map(|x| (x.id, x))
+            .collect();
+
+        Ok(quantities
+            .iter()
+            .filter_map(|(id, quantity)| {
+                known.get(id).map(|item| BomItem {
+                    item_id: item.id,
+                    item_name: item.human_name.clone(),
+                    minecraft_id: item.minecraft_id.clone(),
+                    ty: item.ty,
+                    quantity: *quantity,
+                })
+            }).collect())
+    }
+}
@@ -0,0 +1,194 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel::prelude::*;
+use fxhash::{FxHashMap, FxHashSet};
+use mccraft_core::graph::{Graph, GraphFormat};
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::web;
+
+/// Graphs stop growing once they have this many nodes
+const MAX_GRAPH_NODES: usize = 500;
+/// How many recipes to follow for an item when the request doesn't say
+pub const DEFAULT_RECIPES_PER_ITEM: usize = 5;
+
+/// How far to expand a crafting graph
+#[derive(Debug, Clone, Copy)]
+pub struct GraphLimits {
+    /// How many recipes deep to go
+    pub depth: usize,
+    /// How many recipes to follow for each item without a chosen recipe
+    pub max_recipes_per_item: usize,
+}
+
+/// Render the crafting graph around an item
+pub struct ExportGraph {
+    pub item: i32,
+    pub limits: GraphLimits,
+    /// Recipes to use for particular items. Items without a choice get every
+    /// recipe that makes them.
+    pub recipes: Vec<web::RecipeChoice>,
+    pub format: GraphFormat,
+}
+
+impl Message for ExportGraph {
+    type Result = QueryResult<String>;
+}
+
+impl Handler<ExportGraph> for DbExecutor {
+    type Result = <ExportGraph as Message>::Result;
+
+    fn handle(&mut self, msg: ExportGraph, _: &mut Self::Context) -> Self::Result {
+        Ok(self
+            .expand_graph(msg.item, msg.limits, &msg.recipes)?
+            .graph
+            .render(msg.format))
+    }
+}
+
+/// Get the crafting tree for an item, with all of its recipes
+pub struct CraftingTree {
+    pub item: i32,
+    pub limits: GraphLimits,
+}
+
+impl Message for CraftingTree {
+    type Result = QueryResult<web::CraftingTree>;
+}
+
+impl Handler<CraftingTree> for DbExecutor {
+    type Result = <CraftingTree as Message>::Result;
+
+    fn handle(&mut self, msg: CraftingTree, _: &mut Self::Context) -> Self::Result {
+        let expansion = self.expand_graph(msg.item, msg.limits, &[])?;
+        let graph = expansion.graph;
+
+        let nodes = graph
+            .items
+            .iter()
+            .map(|(id, name)| web::TreeNode::Item {
+                item_id: *id,
+                item_name: name.clone(),
+            }).chain(graph.recipes.iter().map(|(id, machine)| web::TreeNode::Recipe {
+                recipe_id: *id,
+                machine_name: machine.clone(),
+            })).collect();
+
+        Ok(web::CraftingTree {
+            root: msg.item,
+            nodes,
+            edges: graph.edges,
+            recipes: expansion.recipes,
+            truncated: expansion.truncated,
+        })
+    }
+}
+
+/// The result of expanding the crafting graph around an item
+pub(crate) struct Expansion {
+    pub graph: Graph,
+    /// Every recipe in the graph
+    pub recipes: Vec<web::Recipe>,
+    /// Whether the expansion stopped early because the graph got too big
+    pub truncated: bool,
+}
+
+impl DbExecutor {
+    /// Build the crafting graph for an item, breadth first. Each level is
+    /// loaded in one go, so the number of queries depends only on the depth.
+    /// Items are only expanded once, which keeps cycles from going anywhere.
+    pub(crate) fn expand_graph(
+        &self,
+        item: i32,
+        limits: GraphLimits,
+        choices: &[web::RecipeChoice],
+    ) -> QueryResult<Expansion> {
+        use self::schema::{items, outputs, recipes};
+
+        let refs: Vec<web::RecipeRef> = choices.iter().map(|x| x.recipe_id.clone()).collect();
+        let chosen: FxHashMap<i32, i32> = choices
+            .iter()
+            .zip(self.resolve_recipe_refs(&refs)?)
+            .map(|(choice, recipe_id)| {
+                recipe_id
+                    .map(|recipe_id| (choice.item_id, recipe_id))
+                    .ok_or(diesel::result::Error::NotFound)
+            }).collect::<QueryResult<_>>()?;
+
+        let mut expansion = Expansion {
+            graph: Graph::new(),
+            recipes: Vec::new(),
+            truncated: false,
+        };
+        let name = items::table
+            .find(item)
+            .select(items::human_name)
+            .first::<String>(&self.0)?;
+        expansion.graph.items.insert(item, name);
+
+        let mut seen: FxHashSet<i32> = Default::default();
+        seen.insert(item);
+        let mut frontier = vec![item];
+        for _ in 0..limits.depth {
+            if frontier.is_empty() || expansion.truncated {
+                break;
+            }
+
+            let mut recipe_ids: Vec<i32> = frontier
+                .iter()
+                .filter_map(|x| chosen.get(x).cloned())
+                .collect();
+            let unchosen: Vec<i32> = frontier
+                .iter()
+                .filter(|x| !chosen.contains_key(x))
+                .cloned()
+                .collect();
+            let mut per_item: FxHashMap<i32, usize> = Default::default();
+            for (output, recipe) in outputs::table
+                .inner_join(recipes::table)
+                .filter(outputs::item.eq_any(unchosen))
+                .filter(recipes::hidden.eq(false))
+                .select((outputs::item, outputs::recipe))
+                .order_by(outputs::recipe)
+                .load::<(i32, i32)>(&self.0)?
+            {
+                let count = per_item.entry(output).or_insert(0);
+                if *count < limits.max_recipes_per_item {
+                    *count += 1;
+                    recipe_ids.push(recipe);
+                }
+            }
+            recipe_ids.sort();
+            recipe_ids.dedup();
+            recipe_ids.retain(|x| !expansion.graph.contains_recipe(*x));
+            // Every recipe adds at least one node, so there's no point loading
+            // more than would fit
+            let room = MAX_GRAPH_NODES.saturating_sub(expansion.graph.len());
+            if recipe_ids.len() > room {
+                recipe_ids.truncate(room);
+                expansion.truncated = true;
+            }
+
+            let mut next = Vec::new();
+            for recipe in self.load_recipes(&recipe_ids)? {
+                if expansion.graph.len() >= MAX_GRAPH_NODES {
+                    expansion.truncated = true;
+                    break;
+                }
+
+                // Same choice as the BOM calculation: the first alternative we
+                // have a recipe for, or else the first one
+                let inputs = expansion.graph.add_recipe(&recipe, |items| {
+                    items
+                        .iter()
+                        .position(|x| chosen.contains_key(&x.item_id))
+                        .or(if items.is_empty() { None } else { Some(0) })
+                });
+                next.extend(inputs.into_iter().filter(|x| seen.insert(*x)));
+                expansion.recipes.push(recipe);
+            }
+            frontier = next;
+        }
+
+        Ok(expansion)
+    }
+}
@@ -0,0 +1,184 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel::prelude::*;
+use fxhash::FxHashMap;
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::sql;
+use mccraft_core::web::{self, ItemSpec};
+
+/// List every machine, along with how many recipes it has
+pub struct ListMachines;
+
+impl Message for ListMachines {
+    type Result = QueryResult<Vec<web::MachineSummary>>;
+}
+
+impl Handler<ListMachines> for DbExecutor {
+    type Result = <ListMachines as Message>::Result;
+
+    fn handle(&mut self, _: ListMachines, _: &mut Self::Context) -> Self::Result {
+        use self::schema::machines;
+        use diesel::dsl::sql;
+        use diesel::sql_types::BigInt;
+
+        machines::table
+            .select((
+                machines::id,
+                machines::human_name,
+                machines::minecraft_id,
+                machines::bg_texture,
+                sql::<BigInt>(
+                    "(SELECT COUNT(*) FROM mccraft.recipes \
+                     WHERE recipes.machine = machines.id AND NOT recipes.hidden)",
+                ),
+            )).order_by(machines::human_name)
+            .load::<web::MachineSummary>(&self.0)
+    }
+}
+
+/// Get a page of the recipes performed by a machine, optionally only the ones
+/// that produce or use some item.
+pub struct MachineRecipes {
+    pub machine: i32,
+    pub output: Option<i32>,
+    pub input: Option<i32>,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+impl Message for MachineRecipes {
+    type Result = QueryResult<web::RecipePage>;
+}
+
+impl Handler<MachineRecipes> for DbExecutor {
+    type Result = <MachineRecipes as Message>::Result;
+
+    fn handle(&mut self, msg: MachineRecipes, _: &mut Self::Context) -> Self::Result {
+        use self::schema::{
+            crafting_components, ingredient_group_members, input_slots, outputs, recipes,
+        };
+
+        let mut query = recipes::table
+            .select(recipes::id)
+            .filter(recipes::machine.eq(msg.machine))
+            .filter(recipes::hidden.eq(false))
+            .into_boxed();
+        let mut count_query = recipes::table
+            .filter(recipes::machine.eq(msg.machine))
+            .filter(recipes::hidden.eq(false))
+            .into_boxed();
+
+        if let Some(output) = msg.output {
+            let producing = outputs::table
+                .select(outputs::recipe)
+                .filter(outputs::item.eq(output));
+            query = query.filter(recipes::id.eq_any(producing));
+            count_query = count_query.filter(recipes::id.eq_any(
+                outputs::table
+                    .select(outputs::recipe)
+                    .filter(outputs::item.eq(output)),
+            ));
+        }
+
+        if let Some(input) = msg.input {
+            let using_component = input_slots::table
+                .inner_join(crafting_components::table)
+                .select(input_slots::for_recipe)
+                .filter(crafting_components::item.eq(input));
+            let using_group = input_slots::table
+                .inner_join(
+                    ingredient_group_members::table.on(input_slots::ingredient_group
+                        .eq(ingredient_group_members::ingredient_group.nullable())),
+                ).select(input_slots::for_recipe)
+                .filter(ingredient_group_members::item.eq(input));
+            let ids: Vec<i32> = using_component
+                .load::<i32>(&self.0)?
+                .into_iter()
+                .chain(using_group.load::<i32>(&self.0)?)
+                .collect();
+            query = query.filter(recipes::id.eq_any(ids.clone()));
+            count_query = count_query.filter(recipes::id.eq_any(ids));
+        }
+
+        let total = count_query.count().get_result::<i64>(&self.0)?;
+        let ids = query
+            .order_by(recipes::id)
+            .offset(msg.offset)
+            .limit(msg.limit)
+            .load::<i32>(&self.0)?;
+
+        Ok(web::RecipePage {
+            total,
+            offset: msg.offset,
+            recipes: self.load_recipes(&ids)?,
+        })
+    }
+}
+
+/// Get everything we know about a machine
+pub struct Machine(pub i32);
+
+impl Message for Machine {
+    type Result = QueryResult<web::Machine>;
+}
+
+impl Handler<Machine> for DbExecutor {
+    type Result = <Machine as Message>::Result;
+
+    fn handle(&mut self, msg: Machine, _: &mut Self::Context) -> Self::Result {
+        self.load_machine(msg.0)
+    }
+}
+
+impl DbExecutor {
+    pub(crate) fn load_machine(&self, machine_id: i32) -> QueryResult<web::Machine> {
+        self.load_machines(&[machine_id])?
+            .remove(&machine_id)
+            .ok_or(diesel::result::Error::NotFound)
+    }
+
+    /// Load several machines and their catalysts at once
+    pub(crate) fn load_machines(
+        &self,
+        machine_ids: &[i32],
+    ) -> QueryResult<FxHashMap<i32, web::Machine>> {
+        use self::schema::{items, machine_catalysts, machines};
+
+        let mut catalysts: FxHashMap<i32, Vec<ItemSpec>> = Default::default();
+        for (machine, item) in machine_catalysts::table
+            .inner_join(items::table)
+            .filter(machine_catalysts::machine.eq_any(machine_ids))
+            .order_by(items::id)
+            .select((machine_catalysts::machine, items::all_columns))
+            .load::<(i32, sql::Item)>(&self.0)?
+        {
+            catalysts
+                .entry(machine)
+                .or_insert_with(Vec::new)
+                .push(ItemSpec {
+                    item_id: item.id,
+                    item_name: item.human_name,
+                    minecraft_id: item.minecraft_id,
+                    ty: item.ty,
+                    quantity: 1,
+                });
+        }
+
+        Ok(machines::table
+            .filter(machines::id.eq_any(machine_ids))
+            .load::<sql::Machine>(&self.0)?
+            .into_iter()
+            .map(|machine| {
+                (
+                    machine.id,
+                    web::Machine {
+                        machine_id: machine.id,
+                        machine_name: machine.human_name,
+                        minecraft_id: machine.minecraft_id,
+                        bg_texture: machine.bg_texture,
+                        catalysts: catalysts.remove(&machine.id).unwrap_or_default(),
+                    },
+                )
+            }).collect())
+    }
+}
@@ -3,6 +3,12 @@ use diesel::{Connection, ConnectionError, PgConnection};
 
 pub mod searches;
 pub mod about;
+pub mod admin;
+pub mod bom;
+pub mod graphs;
+pub mod machines;
+pub mod paths;
+pub mod plans;
 
 type DbConn = PgConnection;
 
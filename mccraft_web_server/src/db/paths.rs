@@ -0,0 +1,449 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel::prelude::*;
+use fxhash::{FxHashMap, FxHashSet};
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::web;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Paths with more steps than this aren't considered
+const MAX_PATH_STEPS: usize = 12;
+/// The search gives up after looking at this many partial paths
+const MAX_EXPANSIONS: usize = 20000;
+/// The search stops growing paths once it holds this many of them. Slots
+/// with big ingredient groups make each expansion add many paths at once.
+const MAX_QUEUED_PATHS: usize = 100_000;
+/// The most items whose producers are loaded for one search
+const MAX_LOADED_ITEMS: usize = 20000;
+
+/// How to decide which paths are shortest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathWeight {
+    /// Every recipe counts once
+    Steps,
+    /// Every distinct machine counts once, so paths that stay on one machine
+    /// are preferred
+    Machines,
+}
+
+impl PathWeight {
+    pub fn from_name(name: &str) -> Option<PathWeight> {
+        match name {
+            "steps" => Some(PathWeight::Steps),
+            "machines" => Some(PathWeight::Machines),
+            _ => None,
+        }
+    }
+}
+
+/// Find the shortest chains of recipes that turn one item in to another
+pub struct FindPaths {
+    pub from: i32,
+    pub to: i32,
+    /// How many paths to return
+    pub k: usize,
+    pub weight: PathWeight,
+}
+
+impl Message for FindPaths {
+    type Result = QueryResult<web::CraftingPaths>;
+}
+
+/// A recipe that makes some item
+#[derive(Debug, Clone)]
+struct ProducerEdge {
+    recipe_id: i32,
+    machine_id: i32,
+    /// Everything the recipe accepts as an input
+    inputs: Vec<i32>,
+}
+
+/// A step in a partial path, before names have been attached
+#[derive(Debug, Clone, Copy)]
+struct RawStep {
+    recipe_id: i32,
+    machine_id: i32,
+    input: i32,
+    output: i32,
+}
+
+/// A partial path, which runs backwards from the target to `item`
+#[derive(Debug, Clone)]
+struct PartialPath {
+    cost: i64,
+    item: i32,
+    steps: Vec<RawStep>,
+}
+
+impl PartialPath {
+    fn visits(&self, item: i32) -> bool {
+        self.steps.iter().any(|x| x.output == item || x.input == item)
+    }
+
+    fn uses_machine(&self, machine_id: i32) -> bool {
+        self.steps.iter().any(|x| x.machine_id == machine_id)
+    }
+
+    /// The machines the path has used so far, which is all that matters for
+    /// the cost of the rest of it under `PathWeight::Machines`
+    fn machines(&self) -> Vec<i32> {
+        let mut machines: Vec<i32> = self.steps.iter().map(|x| x.machine_id).collect();
+        machines.sort();
+        machines.dedup();
+        machines
+    }
+}
+
+/// The paths a search found
+#[derive(Debug)]
+struct PathSearch {
+    paths: Vec<PartialPath>,
+    /// Whether the search gave up early, so shorter paths may be missing
+    truncated: bool,
+}
+
+impl Handler<FindPaths> for DbExecutor {
+    type Result = <FindPaths as Message>::Result;
+
+    fn handle(&mut self, msg: FindPaths, _: &mut Self::Context) -> Self::Result {
+        // Search backwards from the target, since items tend to have far fewer
+        // producers than uses. Everything within reach is loaded a level at a
+        // time, so the number of queries depends only on the path length.
+        let mut producers: FxHashMap<i32, Vec<ProducerEdge>> = Default::default();
+        let mut frontier = vec![msg.to];
+        let mut truncated = false;
+        for _ in 0..MAX_PATH_STEPS {
+            if frontier.is_empty() {
+                break;
+            }
+            if producers.len() + frontier.len() > MAX_LOADED_ITEMS {
+                truncated = true;
+                break;
+            }
+
+            let level = self.load_producer_edges(&frontier)?;
+            let mut next: FxHashSet<i32> = Default::default();
+            for edges in level.values() {
+                for edge in edges {
+                    next.extend(edge.inputs.iter().filter(|x| {
+                        !producers.contains_key(x) && !level.contains_key(x)
+                    }));
+                }
+            }
+            producers.extend(level);
+            frontier = next.into_iter().collect();
+        }
+
+        let search = find_paths(&producers, msg.from, msg.to, msg.k, msg.weight);
+        Ok(web::CraftingPaths {
+            paths: self.name_paths(search.paths)?,
+            truncated: truncated || search.truncated,
+        })
+    }
+}
+
+/// Find the `k` cheapest paths from `from` to `to`, given the recipes that
+/// make each item. Items missing from `producers` are treated as having no
+/// recipes.
+fn find_paths(
+    producers: &FxHashMap<i32, Vec<ProducerEdge>>,
+    from: i32,
+    to: i32,
+    k: usize,
+    weight: PathWeight,
+) -> PathSearch {
+    // How often partial paths have passed through each item. Under
+    // `PathWeight::Machines` what a path costs from here on also depends on
+    // the machines it has already used, so those are part of the key.
+    let mut pops: FxHashMap<(i32, Vec<i32>), usize> = Default::default();
+
+    // Partial paths are kept in a list, and the heap holds their cost along
+    // with a sequence number to keep the order stable.
+    let mut paths = vec![PartialPath {
+        cost: 0,
+        item: to,
+        steps: Vec::new(),
+    }];
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse((0, 0, 0usize)));
+
+    let mut found = Vec::new();
+    let mut truncated = false;
+    let mut expansions = 0;
+    while let Some(Reverse((_, _, index))) = heap.pop() {
+        let path = paths[index].clone();
+        if path.item == from {
+            found.push(path);
+            if found.len() >= k {
+                break;
+            }
+            continue;
+        }
+
+        // Each item only needs to be passed through k times, since any later
+        // path through it can't be one of the k shortest
+        let key = match weight {
+            PathWeight::Steps => (path.item, Vec::new()),
+            PathWeight::Machines => (path.item, path.machines()),
+        };
+        let popped = pops.entry(key).or_insert(0);
+        *popped += 1;
+        if *popped > k || path.steps.len() >= MAX_PATH_STEPS {
+            continue;
+        }
+        expansions += 1;
+        if expansions > MAX_EXPANSIONS {
+            truncated = true;
+            break;
+        }
+
+        let edges = match producers.get(&path.item) {
+            Some(edges) => edges,
+            None => continue,
+        };
+        for edge in edges {
+            let step_cost = match weight {
+                PathWeight::Steps => 1,
+                PathWeight::Machines if path.uses_machine(edge.machine_id) => 0,
+                PathWeight::Machines => 1,
+            };
+            for input in &edge.inputs {
+                if *input == to || path.visits(*input) {
+                    continue;
+                }
+                if paths.len() >= MAX_QUEUED_PATHS {
+                    truncated = true;
+                    break;
+                }
+
+                let mut next = path.clone();
+                next.cost += step_cost;
+                next.item = *input;
+                next.steps.push(RawStep {
+                    recipe_id: edge.recipe_id,
+                    machine_id: edge.machine_id,
+                    input: *input,
+                    output: path.item,
+                });
+                heap.push(Reverse((next.cost, next.steps.len(), paths.len())));
+                paths.push(next);
+            }
+        }
+    }
+
+    PathSearch {
+        paths: found,
+        truncated,
+    }
+}
+
+impl DbExecutor {
+    /// Load every recipe that makes each of `items`, along with everything
+    /// those recipes accept as inputs
+    fn load_producer_edges(&self, items: &[i32]) -> QueryResult<FxHashMap<i32, Vec<ProducerEdge>>> {
+        use self::schema::{
+            crafting_components, ingredient_group_members, input_slots, outputs, recipes,
+        };
+
+        let produced = outputs::table
+            .inner_join(recipes::table)
+            .filter(outputs::item.eq_any(items))
+            .filter(recipes::hidden.eq(false))
+            .select((outputs::item, recipes::id, recipes::machine))
+            .load::<(i32, i32, i32)>(&self.0)?;
+        let recipe_ids: Vec<i32> = produced.iter().map(|x| x.1).collect();
+
+        let mut inputs: FxHashMap<i32, Vec<i32>> = Default::default();
+        let components = crafting_components::table
+            .inner_join(input_slots::table)
+            .filter(input_slots::for_recipe.eq_any(&recipe_ids))
+            .select((input_slots::for_recipe, crafting_components::item))
+            .load::<(i32, i32)>(&self.0)?;
+        let grouped = ingredient_group_members::table
+            .inner_join(
+                input_slots::table.on(input_slots::ingredient_group
+                    .eq(ingredient_group_members::ingredient_group.nullable())),
+            ).filter(input_slots::for_recipe.eq_any(&recipe_ids))
+            .select((input_slots::for_recipe, ingredient_group_members::item))
+            .load::<(i32, i32)>(&self.0)?;
+        for (recipe, item) in components.into_iter().chain(grouped.into_iter()) {
+            inputs.entry(recipe).or_insert_with(Vec::new).push(item);
+        }
+        for items in inputs.values_mut() {
+            items.sort();
+            items.dedup();
+        }
+
+        // Every requested item gets an entry, even if nothing makes it
+        let mut edges: FxHashMap<i32, Vec<ProducerEdge>> =
+            items.iter().map(|x| (*x, Vec::new())).collect();
+        for (item, recipe_id, machine_id) in produced {
+            edges.entry(item).or_insert_with(Vec::new).push(ProducerEdge {
+                recipe_id,
+                machine_id,
+                inputs: inputs.get(&recipe_id).cloned().unwrap_or_default(),
+            });
+        }
+
+        Ok(edges)
+    }
+
+    /// Attach item and machine names to the paths we found, and turn them
+    /// around so they run from the starting item to the target
+    fn name_paths(&self, paths: Vec<PartialPath>) -> QueryResult<Vec<web::CraftingPath>> {
+        use self::schema::{items, machines};
+
+        let item_ids: Vec<i32> = paths
+            .iter()
+            .flat_map(|x| x.steps.iter().flat_map(|s| vec![s.input, s.output]))
+            .collect();
+        let machine_ids: Vec<i32> = paths
+            .iter()
+            .flat_map(|x| x.steps.iter().map(|s| s.machine_id))
+            .collect();
+        let item_names: FxHashMap<i32, String> = items::table
+            .filter(items::id.eq_any(item_ids))
+            .select((items::id, items::human_name))
+            .load::<(i32, String)>(&self.0)?
+            .into_iter()
+            .collect();
+        let machine_names: FxHashMap<i32, String> = machines::table
+            .filter(machines::id.eq_any(machine_ids))
+            .select((machines::id, machines::human_name))
+            .load::<(i32, String)>(&self.0)?
+            .into_iter()
+            .collect();
+
+        let name = |names: &FxHashMap<i32, String>, id: i32| {
+            names.get(&id).cloned().unwrap_or_default()
+        };
+        Ok(paths
+            .into_iter()
+            .map(|path| web::CraftingPath {
+                cost: path.cost,
+                steps: path
+                    .steps
+                    .iter()
+                    .rev()
+                    .map(|step| web::PathStep {
+                        recipe_id: step.recipe_id,
+                        machine_id: step.machine_id,
+                        machine_name: name(&machine_names, step.machine_id),
+                        input_id: step.input,
+                        input_name: name(&item_names, step.input),
+                        output_id: step.output,
+                        output_name: name(&item_names, step.output),
+                    }).collect(),
+            }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(recipe_id: i32, machine_id: i32, inputs: &[i32]) -> ProducerEdge {
+        ProducerEdge {
+            recipe_id,
+            machine_id,
+            inputs: inputs.to_vec(),
+        }
+    }
+
+    /// The recipes used by each path, from the start to the target
+    fn recipes(search: &PathSearch) -> Vec<Vec<i32>> {
+        search
+            .paths
+            .iter()
+            .map(|x| x.steps.iter().rev().map(|x| x.recipe_id).collect())
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_k_shortest_paths() {
+        // 1 -> 4 directly (recipe 10), through 2 (11, 12) or through 2 and 3
+        // (11, 13, 14)
+        let mut producers = FxHashMap::default();
+        producers.insert(4, vec![edge(10, 1, &[1]), edge(12, 1, &[2]), edge(14, 1, &[3])]);
+        producers.insert(3, vec![edge(13, 1, &[2])]);
+        producers.insert(2, vec![edge(11, 1, &[1])]);
+
+        let search = find_paths(&producers, 1, 4, 3, PathWeight::Steps);
+        assert!(!search.truncated);
+        assert_eq!(recipes(&search), vec![vec![10], vec![11, 12], vec![11, 13, 14]]);
+        assert_eq!(
+            search.paths.iter().map(|x| x.cost).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let search = find_paths(&producers, 1, 4, 1, PathWeight::Steps);
+        assert_eq!(recipes(&search), vec![vec![10]]);
+    }
+
+    #[test]
+    fn cycles_are_not_followed() {
+        // 2 and 3 can be made from each other, and 3 also from 1
+        let mut producers = FxHashMap::default();
+        producers.insert(4, vec![edge(10, 1, &[2])]);
+        producers.insert(2, vec![edge(11, 1, &[3])]);
+        producers.insert(3, vec![edge(12, 1, &[2, 1])]);
+
+        let search = find_paths(&producers, 1, 4, 5, PathWeight::Steps);
+        assert!(!search.truncated);
+        assert_eq!(recipes(&search), vec![vec![12, 11, 10]]);
+    }
+
+    #[test]
+    fn an_item_reaches_itself_without_steps() {
+        let mut producers = FxHashMap::default();
+        producers.insert(1, vec![edge(10, 1, &[2])]);
+        producers.insert(2, vec![edge(11, 1, &[1])]);
+
+        let search = find_paths(&producers, 1, 1, 3, PathWeight::Steps);
+        assert_eq!(search.paths.len(), 1);
+        assert_eq!(search.paths[0].cost, 0);
+        assert!(search.paths[0].steps.is_empty());
+    }
+
+    #[test]
+    fn machines_weight_prefers_staying_on_one_machine() {
+        // Three steps on machine 1, or two steps on machines 1 and 2
+        let mut producers = FxHashMap::default();
+        producers.insert(5, vec![edge(10, 1, &[4]), edge(20, 2, &[3])]);
+        producers.insert(4, vec![edge(11, 1, &[2])]);
+        producers.insert(2, vec![edge(12, 1, &[1])]);
+        producers.insert(3, vec![edge(21, 1, &[1])]);
+
+        let search = find_paths(&producers, 1, 5, 1, PathWeight::Steps);
+        assert_eq!(recipes(&search), vec![vec![21, 20]]);
+
+        let search = find_paths(&producers, 1, 5, 2, PathWeight::Machines);
+        assert_eq!(recipes(&search), vec![vec![12, 11, 10], vec![21, 20]]);
+        assert_eq!(
+            search.paths.iter().map(|x| x.cost).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn machines_weight_keeps_paths_through_a_busy_item() {
+        // Item 2 is reached on machines 2 and 3 before machine 1, but only the
+        // path that used machine 1 can finish cheaply. Pruning by item alone
+        // with k = 1 would throw it away.
+        let mut producers = FxHashMap::default();
+        producers.insert(
+            9,
+            vec![edge(30, 2, &[6]), edge(31, 3, &[7]), edge(32, 1, &[8])],
+        );
+        producers.insert(6, vec![edge(40, 2, &[2])]);
+        producers.insert(7, vec![edge(41, 3, &[2])]);
+        producers.insert(8, vec![edge(42, 1, &[3])]);
+        producers.insert(3, vec![edge(43, 1, &[2])]);
+        producers.insert(2, vec![edge(50, 1, &[1])]);
+
+        let search = find_paths(&producers, 1, 9, 1, PathWeight::Machines);
+        assert_eq!(recipes(&search), vec![vec![50, 43, 42, 32]]);
+        assert_eq!(search.paths[0].cost, 1);
+    }
+}
@@ -0,0 +1,207 @@
+use super::DbExecutor;
+use actix::prelude::*;
+use diesel;
+use diesel::prelude::*;
+use fxhash::FxHashMap;
+use mccraft_core::export::Format;
+use mccraft_core::schema::mccraft as schema;
+use mccraft_core::sql;
+use mccraft_core::web;
+use rand::distributions::Alphanumeric;
+use rand::{self, Rng};
+
+/// How long the randomly generated plan slugs are
+const SLUG_LENGTH: usize = 10;
+
+/// Save a new plan, giving it a fresh slug
+pub struct CreatePlan(pub web::PlanContents);
+
+impl Message for CreatePlan {
+    type Result = QueryResult<web::Plan>;
+}
+
+impl Handler<CreatePlan> for DbExecutor {
+    type Result = <CreatePlan as Message>::Result;
+
+    fn handle(&mut self, msg: CreatePlan, _: &mut Self::Context) -> Self::Result {
+        use self::schema::plans;
+
+        let slug: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SLUG_LENGTH)
+            .collect();
+
+        self.0.transaction(|| {
+            let plan_id = diesel::insert_into(plans::table)
+                .values(&sql::NewPlan {
+                    slug: &slug,
+                    name: &msg.0.name,
+                }).returning(plans::id)
+                .get_result::<i32>(&self.0)?;
+            self.store_plan_contents(plan_id, &msg.0)?;
+            self.load_plan(&slug)
+        })
+    }
+}
+
+/// Retrieve a saved plan by its slug
+pub struct GetPlan(pub String);
+
+impl Message for GetPlan {
+    type Result = QueryResult<web::Plan>;
+}
+
+impl Handler<GetPlan> for DbExecutor {
+    type Result = <GetPlan as Message>::Result;
+
+    fn handle(&mut self, msg: GetPlan, _: &mut Self::Context) -> Self::Result {
+        self.load_plan(&msg.0)
+    }
+}
+
+/// Replace the contents of an existing plan
+pub struct UpdatePlan {
+    pub slug: String,
+    pub contents: web::PlanContents,
+}
+
+impl Message for UpdatePlan {
+    type Result = QueryResult<web::Plan>;
+}
+
+impl Handler<UpdatePlan> for DbExecutor {
+    type Result = <UpdatePlan as Message>::Result;
+
+    fn handle(&mut self, msg: UpdatePlan, _: &mut Self::Context) -> Self::Result {
+        use self::schema::{plan_recipes, plan_targets, plans};
+
+        self.0.transaction(|| {
+            let plan_id = diesel::update(plans::table.filter(plans::slug.eq(&msg.slug)))
+                .set(plans::name.eq(&msg.contents.name))
+                .returning(plans::id)
+                .get_result::<i32>(&self.0)?;
+
+            diesel::delete(plan_targets::table.filter(plan_targets::plan.eq(plan_id)))
+                .execute(&self.0)?;
+            diesel::delete(plan_recipes::table.filter(plan_recipes::plan.eq(plan_id)))
+                .execute(&self.0)?;
+            self.store_plan_contents(plan_id, &msg.contents)?;
+
+            self.load_plan(&msg.slug)
+        })
+    }
+}
+
+/// Compute the bill of materials for a saved plan, and render it in one of
+/// the export formats
+pub struct ExportPlan {
+    pub slug: String,
+    pub format: Format,
+}
+
+impl Message for ExportPlan {
+    type Result = QueryResult<String>;
+}
+
+impl Handler<ExportPlan> for DbExecutor {
+    type Result = <ExportPlan as Message>::Result;
+
+    fn handle(&mut self, msg: ExportPlan, _: &mut Self::Context) -> Self::Result {
+        let plan = self.load_plan(&msg.slug)?;
+        let request = web::BomRequest {
+            targets: plan.targets,
+            recipes: plan.recipes,
+            include_machines: false,
+        };
+        self.export_bom(&plan.name, &request, msg.format)
+    }
+}
+
+impl DbExecutor {
+    /// Write the targets and recipe choices of a plan. Recipes are stored by
+    /// their content hash, so every chosen recipe has to have one.
+    fn store_plan_contents(&self, plan_id: i32, contents: &web::PlanContents) -> QueryResult<()> {
+        use self::schema::{plan_recipes, plan_targets, recipes};
+
+        let refs: Vec<web::RecipeRef> = contents
+            .recipes
+            .iter()
+            .map(|x| x.recipe_id.clone())
+            .collect();
+        let recipe_ids = self
+            .resolve_recipe_refs(&refs)?
+            .into_iter()
+            .collect::<Option<Vec<i32>>>()
+            .ok_or(diesel::result::Error::NotFound)?;
+        let hashes: FxHashMap<i32, String> = recipes::table
+            .filter(recipes::id.eq_any(&recipe_ids))
+            .select((recipes::id, recipes::content_hash))
+            .load::<(i32, Option<String>)>(&self.0)?
+            .into_iter()
+            .filter_map(|(id, hash)| hash.map(|hash| (id, hash)))
+            .collect();
+
+        let mut new_recipes = Vec::with_capacity(contents.recipes.len());
+        for (choice, recipe_id) in contents.recipes.iter().zip(recipe_ids.iter()) {
+            new_recipes.push(sql::NewPlanRecipe {
+                plan: plan_id,
+                item: choice.item_id,
+                recipe_hash: hashes
+                    .get(recipe_id)
+                    .ok_or(diesel::result::Error::NotFound)?,
+            });
+        }
+        diesel::insert_into(plan_recipes::table)
+            .values(&new_recipes)
+            .execute(&self.0)?;
+
+        let new_targets: Vec<_> = contents
+            .targets
+            .iter()
+            .map(|target| sql::NewPlanTarget {
+                plan: plan_id,
+                item: target.item_id,
+                quantity: target.quantity,
+            }).collect();
+        diesel::insert_into(plan_targets::table)
+            .values(&new_targets)
+            .execute(&self.0)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn load_plan(&self, slug: &str) -> QueryResult<web::Plan> {
+        use self::schema::{plan_recipes, plan_targets, plans};
+
+        let plan = plans::table
+            .filter(plans::slug.eq(slug))
+            .first::<sql::Plan>(&self.0)?;
+
+        let targets = plan_targets::table
+            .filter(plan_targets::plan.eq(plan.id))
+            .order_by(plan_targets::id)
+            .load::<sql::PlanTarget>(&self.0)?
+            .into_iter()
+            .map(|target| web::ItemQuantity {
+                item_id: target.item,
+                quantity: target.quantity,
+            }).collect();
+
+        let recipes = plan_recipes::table
+            .filter(plan_recipes::plan.eq(plan.id))
+            .order_by(plan_recipes::id)
+            .load::<sql::PlanRecipe>(&self.0)?
+            .into_iter()
+            .map(|choice| web::RecipeChoice {
+                item_id: choice.item,
+                recipe_id: web::RecipeRef::Hash(choice.recipe_hash),
+            }).collect();
+
+        Ok(web::Plan {
+            slug: plan.slug,
+            name: plan.name,
+            targets,
+            recipes,
+        })
+    }
+}
@@ -3,7 +3,7 @@ use actix_web::actix::*;
 use diesel::prelude::*;
 use mccraft_core::schema::mccraft as schema;
 use mccraft_core::sql;
-use mccraft_core::web::PartialRecipe;
+use mccraft_core::web::{self, PartialRecipe};
 
 /// Search the outputs of all recipes
 pub enum SearchOutputs {
@@ -16,6 +16,29 @@ impl Message for SearchOutputs {
 }
 
 impl DbExecutor {
+    /// Get the IDs of every item that should be treated as the same as `id`.
+    /// Filled fluid containers are equivalent to the fluid they hold, and vice
+    /// versa.
+    pub(crate) fn equivalent_items(&self, id: i32) -> QueryResult<Vec<i32>> {
+        use self::schema::fluid_containers;
+
+        let mut ids = vec![id];
+        ids.extend(
+            fluid_containers::table
+                .filter(fluid_containers::fluid.eq(id))
+                .select(fluid_containers::filled_item)
+                .load::<i32>(&self.0)?,
+        );
+        ids.extend(
+            fluid_containers::table
+                .filter(fluid_containers::filled_item.eq(id))
+                .select(fluid_containers::fluid)
+                .load::<i32>(&self.0)?,
+        );
+
+        Ok(ids)
+    }
+
     fn handle_search_outputs_by_name(
         &self,
         mut query: String,
@@ -28,6 +51,7 @@ impl DbExecutor {
             .inner_join(outputs::table.on(outputs::recipe.eq(recipes::id)))
             .inner_join(items::table.on(outputs::item.eq(items::id)))
             .filter(items::human_name.ilike(query))
+            .filter(recipes::hidden.eq(false))
             .select((machines::id, machines::human_name, recipes::id))
             .load::<(i32, String, i32)>(&self.0)?
             .into_iter()
@@ -40,10 +64,12 @@ impl DbExecutor {
 
     fn handle_search_outputs_by_id(&self, id: i32) -> <SearchOutputs as Message>::Result {
         use self::schema::{machines, outputs, recipes};
+        let ids = self.equivalent_items(id)?;
         Ok(machines::table
             .inner_join(recipes::table.on(recipes::machine.eq(machines::id)))
             .inner_join(outputs::table.on(outputs::recipe.eq(recipes::id)))
-            .filter(outputs::item.eq(id))
+            .filter(outputs::item.eq_any(ids))
+            .filter(recipes::hidden.eq(false))
             .select((machines::id, machines::human_name, recipes::id))
             .load::<(i32, String, i32)>(&self.0)?
             .into_iter()
@@ -66,6 +92,83 @@ impl Handler<SearchOutputs> for DbExecutor {
     }
 }
 
+/// Find every recipe that produces an item, with all of their inputs and
+/// outputs filled in.
+pub struct ProducerRecipes(pub i32);
+
+impl Message for ProducerRecipes {
+    type Result = QueryResult<Vec<web::Recipe>>;
+}
+
+impl Handler<ProducerRecipes> for DbExecutor {
+    type Result = <ProducerRecipes as Message>::Result;
+
+    fn handle(&mut self, msg: ProducerRecipes, _: &mut Self::Context) -> Self::Result {
+        let ids: Vec<i32> = self
+            .handle_search_outputs_by_id(msg.0)?
+            .into_iter()
+            .map(|x| x.recipe_id)
+            .collect();
+        self.load_recipes(&ids)
+    }
+}
+
+/// Search the inputs of all recipes. That is, find everything an item can be
+/// used for.
+pub struct SearchInputs(pub i32);
+
+impl Message for SearchInputs {
+    type Result = QueryResult<Vec<PartialRecipe>>;
+}
+
+impl Handler<SearchInputs> for DbExecutor {
+    type Result = <SearchInputs as Message>::Result;
+
+    fn handle(&mut self, msg: SearchInputs, _: &mut Self::Context) -> Self::Result {
+        use self::schema::{
+            crafting_components, ingredient_group_members, input_slots, machines, recipes,
+        };
+        let ids = self.equivalent_items(msg.0)?;
+
+        let mut found = machines::table
+            .inner_join(recipes::table.on(recipes::machine.eq(machines::id)))
+            .inner_join(input_slots::table.on(input_slots::for_recipe.eq(recipes::id)))
+            .inner_join(
+                crafting_components::table
+                    .on(crafting_components::crafting_slot.eq(input_slots::id)),
+            ).filter(crafting_components::item.eq_any(&ids))
+            .filter(recipes::hidden.eq(false))
+            .select((machines::id, machines::human_name, recipes::id))
+            .distinct()
+            .load::<(i32, String, i32)>(&self.0)?;
+
+        // Slots that use an ingredient group don't have crafting components
+        found.extend(
+            machines::table
+                .inner_join(recipes::table.on(recipes::machine.eq(machines::id)))
+                .inner_join(input_slots::table.on(input_slots::for_recipe.eq(recipes::id)))
+                .inner_join(
+                    ingredient_group_members::table.on(input_slots::ingredient_group
+                        .eq(ingredient_group_members::ingredient_group.nullable())),
+                ).filter(ingredient_group_members::item.eq_any(&ids))
+                .filter(recipes::hidden.eq(false))
+                .select((machines::id, machines::human_name, recipes::id))
+                .distinct()
+                .load::<(i32, String, i32)>(&self.0)?,
+        );
+        found.sort_by_key(|x| x.2);
+        found.dedup_by_key(|x| x.2);
+
+        Ok(found
+            .into_iter()
+            .map(|(mid, mn, rid)| PartialRecipe {
+                machine_id: mid,
+                machine_name: mn,
+                recipe_id: rid,
+            }).collect())
+    }
+}
+
 pub struct SearchItems {
     pub name: String,
     pub limit: i64,
@@ -80,16 +183,17 @@ impl Handler<SearchItems> for DbExecutor {
     type Result = <SearchItems as Message>::Result;
 
     fn handle(&mut self, mut msg: SearchItems, _: &mut Self::Context) -> Self::Result {
-        use self::schema::{items, outputs};
+        use self::schema::{items, outputs, recipes};
         msg.name.push('%');
         Ok(items::table
-            .inner_join(outputs::table)
+            .inner_join(outputs::table.inner_join(recipes::table))
             .filter(items::human_name.ilike(msg.name))
+            .filter(recipes::hidden.eq(false))
             .limit(msg.limit)
             .offset(msg.offset)
             .order_by((items::human_name, items::id))
             .distinct_on((items::human_name, items::id))
-            .select((items::id, items::ty, items::human_name, items::minecraft_id))
+            .select(items::all_columns)
             .load::<sql::Item>(&self.0)?)
     }
 }
@@ -0,0 +1,224 @@
+//! Imports of uploaded JEI exports.
+//!
+//! Importing a pack takes minutes, so uploads are unpacked and imported on a
+//! thread of their own while the admin polls the job for progress. Only one
+//! import runs at a time, since they all write to the same tables.
+
+use diesel::{Connection, PgConnection};
+use mccraft_importer::report::ImportReport;
+use mccraft_importer::types::MCCraftError;
+use mccraft_importer::{
+    archive, consumption, containers, overrides, raw_items, ExportSource, IngestOptions,
+    Progress, Stage, WriteOptions,
+};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Largest an uploaded export may be once it's unpacked
+const MAX_EXTRACTED_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Everything there is to know about an import, past or present
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportJob {
+    pub id: u32,
+    pub status: JobStatus,
+    pub stage: Stage,
+    pub logs: Vec<String>,
+    /// What the import did, once it has succeeded
+    pub report: Option<ImportReport>,
+    /// Why the import failed, if it did
+    pub error: Option<String>,
+}
+
+struct JobTable {
+    next_id: u32,
+    jobs: BTreeMap<u32, ImportJob>,
+}
+
+/// Every import started since the server came up
+#[derive(Clone)]
+pub struct ImportJobs {
+    database_url: String,
+    table: Arc<Mutex<JobTable>>,
+}
+
+impl ImportJobs {
+    pub fn new(database_url: String) -> ImportJobs {
+        ImportJobs {
+            database_url,
+            table: Arc::new(Mutex::new(JobTable {
+                next_id: 1,
+                jobs: BTreeMap::new(),
+            })),
+        }
+    }
+
+    /// Start importing the export in `archive`, which is a zip or a .tar.gz.
+    /// Returns None if another import is still running.
+    pub fn start(&self, archive: Vec<u8>) -> Option<ImportJob> {
+        let job = {
+            let mut table = self.table.lock().unwrap();
+            if table
+                .jobs
+                .values()
+                .any(|x| x.status == JobStatus::Running)
+            {
+                return None;
+            }
+
+            let id = table.next_id;
+            table.next_id += 1;
+            let job = ImportJob {
+                id,
+                status: JobStatus::Running,
+                stage: Stage::Extracting,
+                logs: Vec::new(),
+                report: None,
+                error: None,
+            };
+            table.jobs.insert(id, job.clone());
+            job
+        };
+
+        let mut progress = JobProgress {
+            id: job.id,
+            table: self.table.clone(),
+        };
+        let database_url = self.database_url.clone();
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                run_import(&archive, &database_url, &mut progress)
+            })).unwrap_or_else(|panic| Err(panic_message(panic)));
+            progress.finish(result);
+        });
+
+        Some(job)
+    }
+
+    pub fn get(&self, id: u32) -> Option<ImportJob> {
+        self.table.lock().unwrap().jobs.get(&id).cloned()
+    }
+}
+
+/// Records progress on the job it's importing for
+struct JobProgress {
+    id: u32,
+    table: Arc<Mutex<JobTable>>,
+}
+
+impl JobProgress {
+    fn update(&self, f: impl FnOnce(&mut ImportJob)) {
+        // Keep recording progress even if something panicked while holding
+        // the lock
+        let mut table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = table.jobs.get_mut(&self.id) {
+            f(job);
+        }
+    }
+
+    fn finish(&self, result: Result<ImportReport, String>) {
+        match result {
+            Ok(report) => {
+                info!("Import {} succeeded", self.id);
+                self.update(|job| {
+                    job.status = JobStatus::Succeeded;
+                    job.report = Some(report);
+                });
+            }
+            Err(e) => {
+                error!("Import {} failed: {}", self.id, e);
+                self.update(|job| {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e);
+                });
+            }
+        }
+    }
+}
+
+impl Progress for JobProgress {
+    fn stage(&mut self, stage: Stage) {
+        info!("Import {}: {:?}", self.id, stage);
+        self.update(|job| job.stage = stage);
+    }
+
+    fn log(&mut self, message: String) {
+        info!("Import {}: {}", self.id, message);
+        self.update(|job| job.logs.push(message));
+    }
+}
+
+/// Load the same configuration files as the command line importer. They're
+/// read again for every job so that edits don't need a restart.
+fn load_options() -> Result<(IngestOptions, WriteOptions), MCCraftError> {
+    let ingest = IngestOptions {
+        recipe_overrides: overrides::load(std::env::var_os("MCCRAFT_RECIPE_OVERRIDES"))?,
+        consumption_overrides: consumption::load(std::env::var_os(
+            "MCCRAFT_CONSUMPTION_OVERRIDES",
+        ))?,
+    };
+    let write = WriteOptions {
+        fluid_containers: containers::load(std::env::var_os("MCCRAFT_FLUID_CONTAINERS"))?,
+        raw_items: raw_items::load(std::env::var_os("MCCRAFT_RAW_ITEMS"))?,
+        purge_first: false,
+        // The site keeps serving while the import runs
+        drop_constraints: false,
+    };
+    Ok((ingest, write))
+}
+
+fn run_import(
+    archive: &[u8],
+    database_url: &str,
+    progress: &mut JobProgress,
+) -> Result<ImportReport, String> {
+    progress.stage(Stage::Extracting);
+    let folder = std::env::temp_dir().join(format!(
+        "mccraft-import-{}-{}",
+        std::process::id(),
+        progress.id
+    ));
+    let result = archive::extract(archive, &folder, MAX_EXTRACTED_SIZE)
+        .map_err(|e| format!("Failed to extract the export: {:?}", e))
+        .and_then(|base_folder| {
+            progress.log(format!("Extracted the export to {}", base_folder.display()));
+            let (ingest_options, write_options) = load_options()
+                .map_err(|e| format!("Failed to load the importer configuration: {:?}", e))?;
+            let conn = PgConnection::establish(database_url)
+                .map_err(|e| format!("Failed to connect to the database: {}", e))?;
+            mccraft_importer::import(
+                &ExportSource::Folder(base_folder),
+                &conn,
+                &ingest_options,
+                &write_options,
+                progress,
+            ).map_err(|e| format!("Failed to import the export: {:?}", e))
+        });
+
+    if let Err(e) = std::fs::remove_dir_all(&folder) {
+        warn!("Failed to clean up {}: {}", folder.display(), e);
+    }
+    result
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = match panic.downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match panic.downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "unknown error".to_owned(),
+        },
+    };
+    format!("The import panicked: {}", message)
+}
@@ -10,24 +10,71 @@ extern crate fxhash;
 #[macro_use]
 extern crate log;
 extern crate mccraft_core;
+extern crate mccraft_importer;
+extern crate rand;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
 pub mod db;
+pub mod imports;
 // pub mod future_helpers;
 
 use actix::prelude::*;
 use actix_web::{
-    http, server, App, AsyncResponder, FromRequest, HttpRequest, HttpResponse, Path, Query,
-    Responder,
+    http, server, App, AsyncResponder, FromRequest, HttpMessage, HttpRequest, HttpResponse, Json,
+    Path, Query, Responder,
 };
+use db::graphs::{GraphLimits, DEFAULT_RECIPES_PER_ITEM};
+use db::paths::PathWeight;
 use futures::Future;
+use mccraft_core::export::Format;
+use mccraft_core::graph::GraphFormat;
+use mccraft_core::web::{RecipeChoice, RecipeRef};
 use std::fmt;
 use std::path::PathBuf;
 
 struct AppState {
     db: Addr<db::DbExecutor>,
+    /// Token that has to be presented to use the admin endpoints. The admin
+    /// endpoints are disabled if this isn't set.
+    admin_token: Option<String>,
+    imports: imports::ImportJobs,
+}
+
+/// Largest export archive that can be uploaded for import
+const MAX_IMPORT_SIZE: usize = 256 * 1024 * 1024;
+
+/// Make sure the request carries the admin token as a bearer token
+fn check_admin(req: &HttpRequest<AppState>) -> Result<(), actix_web::Error> {
+    let expected = match req.state().admin_token {
+        Some(ref token) => token,
+        None => return Err(actix_web::error::ErrorNotFound("Admin endpoints are disabled")),
+    };
+    let provided = req
+        .headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| {
+            if x.starts_with("Bearer ") {
+                Some(&x["Bearer ".len()..])
+            } else {
+                None
+            }
+        });
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => Ok(()),
+        _ => Err(actix_web::error::ErrorUnauthorized("Missing or incorrect admin token")),
+    }
+}
+
+/// Compare two byte strings without leaking where they differ through timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
 }
 
 fn json_response<T: serde::Serialize, E: fmt::Debug>(
@@ -41,16 +88,89 @@ fn json_response<T: serde::Serialize, E: fmt::Debug>(
     }
 }
 
+fn text_response<E: fmt::Debug>(
+    format: Format,
+    v: Result<String, E>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match v {
+        Ok(v) => Ok(HttpResponse::Ok().content_type(format.content_type()).body(v)),
+        Err(e) => {
+            Ok(HttpResponse::InternalServerError().body(format!("Internal server error: {:?}", e)))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormatRequest {
+    format: Option<String>,
+}
+
+/// Work out which format the client wants a response in, from the `format=`
+/// parameter or, failing that, the `Accept` header
+fn export_format(req: &HttpRequest<AppState>) -> Result<Format, actix_web::Error> {
+    if let Some(name) = Query::<FormatRequest>::extract(req)?.into_inner().format {
+        return Format::from_name(&name).ok_or_else(|| {
+            actix_web::error::ErrorBadRequest(format!("Unknown format {:?}", name))
+        });
+    }
+
+    let accept = req
+        .headers()
+        .get(http::header::ACCEPT)
+        .and_then(|x| x.to_str().ok())
+        .unwrap_or("");
+    Ok(accept
+        .split(',')
+        .filter_map(Format::from_mime)
+        .next()
+        .unwrap_or(Format::Json))
+}
+
 fn index(_req: &HttpRequest<AppState>) -> impl Responder {
     return HttpResponse::Ok().body(include_str!("../html/index.html"));
 }
 
+/// Recipe batches larger than this are rejected, to keep a single request from
+/// tying up a database connection for too long.
+const MAX_RECIPE_BATCH: usize = 100;
+
+#[derive(Deserialize)]
+pub struct ProducersRequest {
+    expand: Option<String>,
+}
+
 fn recipes_for_item(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let expand = match Query::<ProducersRequest>::extract(req) {
+        Ok(query) => query.into_inner().expand,
+        Err(e) => return futures::future::err::<HttpResponse, _>(e).responder(),
+    };
+
+    if expand.as_ref().map(|x| x.as_str()) == Some("full") {
+        futures::future::result(Path::<i32>::extract(req))
+            .and_then(move |path| {
+                dbref
+                    .send(db::searches::ProducerRecipes(path.into_inner()))
+                    .from_err()
+            }).and_then(json_response)
+            .responder()
+    } else {
+        futures::future::result(Path::<i32>::extract(req))
+            .and_then(move |path| {
+                dbref
+                    .send(db::searches::SearchOutputs::ById(path.into_inner()))
+                    .from_err()
+            }).and_then(json_response)
+            .responder()
+    }
+}
+
+fn uses_for_item(req: &HttpRequest<AppState>) -> impl Responder {
     let dbref = req.state().db.clone();
     futures::future::result(Path::<i32>::extract(req))
         .and_then(move |path| {
             dbref
-                .send(db::searches::SearchOutputs::ById(path.into_inner()))
+                .send(db::searches::SearchInputs(path.into_inner()))
                 .from_err()
         }).and_then(json_response)
         .responder()
@@ -64,10 +184,322 @@ fn item_info(req: &HttpRequest<AppState>) -> impl Responder {
         .responder()
 }
 
+fn item_by_minecraft_id(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<String>::extract(req))
+        .and_then(move |path| {
+            dbref
+                .send(db::about::ItemByMinecraftId(path.into_inner()))
+                .from_err()
+        }).and_then(|v| match v {
+            Ok(None) => Ok(HttpResponse::NotFound().body("No such item")),
+            v => json_response(v),
+        }).responder()
+}
+
+fn lookup_items(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    Json::<mccraft_core::web::ItemLookupRequest>::extract(req)
+        .and_then(move |body| {
+            dbref
+                .send(db::about::LookupItems(body.into_inner().items))
+                .from_err()
+        }).and_then(json_response)
+        .responder()
+}
+
+fn item_variants(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<i32>::extract(req))
+        .and_then(move |path| {
+            dbref
+                .send(db::about::ItemVariants(path.into_inner()))
+                .from_err()
+        }).and_then(json_response)
+        .responder()
+}
+
 fn complete_recipe(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<String>::extract(req))
+        .and_then(move |path| {
+            dbref
+                .send(db::about::Recipe(RecipeRef::parse(&path)))
+                .from_err()
+        })
+        .and_then(json_response)
+        .responder()
+}
+
+#[derive(Deserialize)]
+pub struct RecipesRequest {
+    ids: String,
+}
+
+/// Parse a comma separated list of recipe IDs or hashes
+fn parse_recipe_ids(ids: &str) -> Result<Vec<RecipeRef>, actix_web::Error> {
+    let ids: Vec<_> = ids
+        .split(',')
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| RecipeRef::parse(x.trim()))
+        .collect();
+    if ids.len() > MAX_RECIPE_BATCH {
+        return Err(actix_web::error::ErrorBadRequest(format!(
+            "At most {} recipes can be requested at once",
+            MAX_RECIPE_BATCH
+        )));
+    }
+
+    Ok(ids)
+}
+
+fn complete_recipes(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(
+        Query::<RecipesRequest>::extract(req).and_then(|query| parse_recipe_ids(&query.ids)),
+    ).and_then(move |ids| dbref.send(db::about::Recipes(ids)).from_err())
+    .and_then(json_response)
+    .responder()
+}
+
+fn machine_info(req: &HttpRequest<AppState>) -> impl Responder {
     let dbref = req.state().db.clone();
     futures::future::result(Path::<i32>::extract(req))
-        .and_then(move |path| dbref.send(db::about::Recipe(path.into_inner())).from_err())
+        .and_then(move |path| {
+            dbref
+                .send(db::machines::Machine(path.into_inner()))
+                .from_err()
+        }).and_then(json_response)
+        .responder()
+}
+
+fn list_machines(req: &HttpRequest<AppState>) -> impl Responder {
+    req.state()
+        .db
+        .send(db::machines::ListMachines)
+        .from_err()
+        .and_then(json_response)
+        .responder()
+}
+
+#[derive(Deserialize)]
+pub struct MachineRecipesRequest {
+    output: Option<i32>,
+    input: Option<i32>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+fn machine_recipes(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<i32>::extract(req).and_then(|path| {
+        Query::<MachineRecipesRequest>::extract(req).map(|query| (path, query))
+    })).and_then(move |(path, query)| {
+        let query = query.into_inner();
+        dbref
+            .send(db::machines::MachineRecipes {
+                machine: path.into_inner(),
+                output: query.output,
+                input: query.input,
+                offset: query.offset.unwrap_or(0),
+                limit: query.limit.unwrap_or(20).min(100),
+            }).from_err()
+    }).and_then(json_response)
+    .responder()
+}
+
+fn compute_bom(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let format = match export_format(req) {
+        Ok(format) => format,
+        Err(e) => return futures::future::err::<HttpResponse, _>(e).responder(),
+    };
+
+    if format == Format::Json {
+        Json::<mccraft_core::web::BomRequest>::extract(req)
+            .and_then(move |body| dbref.send(db::bom::ComputeBom(body.into_inner())).from_err())
+            .and_then(json_response)
+            .responder()
+    } else {
+        Json::<mccraft_core::web::BomRequest>::extract(req)
+            .and_then(move |body| {
+                dbref
+                    .send(db::bom::ExportBom {
+                        request: body.into_inner(),
+                        format,
+                    }).from_err()
+            }).and_then(move |v| text_response(format, v))
+            .responder()
+    }
+}
+
+fn create_plan(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    Json::<mccraft_core::web::PlanContents>::extract(req)
+        .and_then(move |body| dbref.send(db::plans::CreatePlan(body.into_inner())).from_err())
+        .and_then(json_response)
+        .responder()
+}
+
+fn get_plan(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let format = match export_format(req) {
+        Ok(format) => format,
+        Err(e) => return futures::future::err::<HttpResponse, _>(e).responder(),
+    };
+
+    if format == Format::Json {
+        futures::future::result(Path::<String>::extract(req))
+            .and_then(move |path| dbref.send(db::plans::GetPlan(path.into_inner())).from_err())
+            .and_then(json_response)
+            .responder()
+    } else {
+        futures::future::result(Path::<String>::extract(req))
+            .and_then(move |path| {
+                dbref
+                    .send(db::plans::ExportPlan {
+                        slug: path.into_inner(),
+                        format,
+                    }).from_err()
+            }).and_then(move |v| text_response(format, v))
+            .responder()
+    }
+}
+
+fn update_plan(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let slug = Path::<String>::extract(req);
+    Json::<mccraft_core::web::PlanContents>::extract(req)
+        .and_then(move |body| slug.map(|slug| (slug.into_inner(), body.into_inner())))
+        .and_then(move |(slug, contents)| {
+            dbref
+                .send(db::plans::UpdatePlan { slug, contents })
+                .from_err()
+        }).and_then(json_response)
+        .responder()
+}
+
+/// Graphs deeper than this are rejected
+const MAX_GRAPH_DEPTH: usize = 10;
+
+/// Parse a comma separated list of `item:recipe` pairs, where the recipe is an
+/// ID or hash
+fn parse_recipe_choices(choices: &str) -> Result<Vec<RecipeChoice>, String> {
+    choices
+        .split(',')
+        .filter(|x| !x.trim().is_empty())
+        .map(|choice| {
+            let mut parts = choice.trim().splitn(2, ':');
+            let item_id = parts.next().and_then(|x| x.parse::<i32>().ok());
+            match (item_id, parts.next()) {
+                (Some(item_id), Some(recipe)) => Ok(RecipeChoice {
+                    item_id,
+                    recipe_id: RecipeRef::parse(recipe),
+                }),
+                _ => Err(format!("Expected item:recipe, got {:?}", choice)),
+            }
+        }).collect()
+}
+
+#[derive(Deserialize)]
+pub struct GraphRequest {
+    depth: Option<usize>,
+    recipes: Option<String>,
+}
+
+fn item_graph(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let request = Path::<(i32, String)>::extract(req).and_then(|path| {
+        let (item, format) = path.into_inner();
+        let format = GraphFormat::from_name(&format).ok_or_else(|| {
+            actix_web::error::ErrorNotFound(format!("Unknown graph format {:?}", format))
+        })?;
+        let query = Query::<GraphRequest>::extract(req)?.into_inner();
+        let recipes = parse_recipe_choices(query.recipes.as_ref().map(|x| x.as_str()).unwrap_or(""))
+            .map_err(actix_web::error::ErrorBadRequest)?;
+
+        Ok(db::graphs::ExportGraph {
+            item,
+            limits: GraphLimits {
+                depth: query.depth.unwrap_or(3).min(MAX_GRAPH_DEPTH),
+                max_recipes_per_item: DEFAULT_RECIPES_PER_ITEM,
+            },
+            recipes,
+            format,
+        })
+    });
+
+    futures::future::result(request)
+        .and_then(move |msg| {
+            let format = msg.format;
+            dbref
+                .send(msg)
+                .from_err()
+                .map(move |v| (format, v))
+        }).and_then(|(format, v)| match v {
+            Ok(v) => Ok(HttpResponse::Ok().content_type(format.content_type()).body(v)),
+            Err(e) => json_response::<(), _>(Err(e)),
+        }).responder()
+}
+
+#[derive(Deserialize)]
+pub struct TreeRequest {
+    depth: Option<usize>,
+    max_recipes_per_item: Option<usize>,
+}
+
+fn crafting_tree(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<i32>::extract(req).and_then(|path| {
+        Query::<TreeRequest>::extract(req).map(|query| (path, query))
+    })).and_then(move |(path, query)| {
+        dbref
+            .send(db::graphs::CraftingTree {
+                item: path.into_inner(),
+                limits: GraphLimits {
+                    depth: query.depth.unwrap_or(3).min(MAX_GRAPH_DEPTH),
+                    max_recipes_per_item: query
+                        .max_recipes_per_item
+                        .unwrap_or(DEFAULT_RECIPES_PER_ITEM)
+                        .max(1),
+                },
+            }).from_err()
+    }).and_then(json_response)
+    .responder()
+}
+
+/// The most paths that can be asked for at once
+const MAX_PATHS: usize = 10;
+
+#[derive(Deserialize)]
+pub struct PathRequest {
+    from: i32,
+    to: i32,
+    k: Option<usize>,
+    weight: Option<String>,
+}
+
+fn crafting_paths(req: &HttpRequest<AppState>) -> impl Responder {
+    let dbref = req.state().db.clone();
+    let request = Query::<PathRequest>::extract(req).and_then(|query| {
+        let query = query.into_inner();
+        let weight = match query.weight {
+            Some(ref name) => PathWeight::from_name(name).ok_or_else(|| {
+                actix_web::error::ErrorBadRequest(format!("Unknown path weight {:?}", name))
+            })?,
+            None => PathWeight::Steps,
+        };
+
+        Ok(db::paths::FindPaths {
+            from: query.from,
+            to: query.to,
+            k: query.k.unwrap_or(3).max(1).min(MAX_PATHS),
+            weight,
+        })
+    });
+
+    futures::future::result(request)
+        .and_then(move |msg| dbref.send(msg).from_err())
         .and_then(json_response)
         .responder()
 }
@@ -94,6 +526,125 @@ fn search_for_item(req: &HttpRequest<AppState>) -> impl Responder {
         .responder()
 }
 
+fn admin_health(req: &HttpRequest<AppState>) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    req.state()
+        .db
+        .send(db::admin::CheckHealth)
+        .from_err()
+        .and_then(json_response)
+        .responder()
+}
+
+#[derive(Deserialize)]
+pub struct ConflictsRequest {
+    /// Comma separated JEI categories whose recipes are compared slot by slot
+    shaped: Option<String>,
+}
+
+fn admin_conflicts(req: &HttpRequest<AppState>) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    let dbref = req.state().db.clone();
+    futures::future::result(Query::<ConflictsRequest>::extract(req))
+        .and_then(move |query| {
+            let shaped_categories = query
+                .shaped
+                .as_ref()
+                .map(|x| {
+                    x.split(',')
+                        .filter(|x| !x.is_empty())
+                        .map(|x| x.to_owned())
+                        .collect()
+                }).unwrap_or_default();
+            dbref
+                .send(db::admin::FindConflicts { shaped_categories })
+                .from_err()
+        }).and_then(json_response)
+        .responder()
+}
+
+fn admin_create_recipe(req: &HttpRequest<AppState>) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    let dbref = req.state().db.clone();
+    Json::<mccraft_core::web::ManualRecipe>::extract(req)
+        .and_then(move |body| {
+            dbref
+                .send(db::admin::CreateRecipe(body.into_inner()))
+                .from_err()
+        }).and_then(|v| match v {
+            Ok(Ok(recipe)) => Ok(HttpResponse::Ok().json(recipe)),
+            Ok(Err(problem)) => Ok(HttpResponse::BadRequest().body(problem)),
+            Err(e) => json_response::<(), _>(Err(e)),
+        }).responder()
+}
+
+fn set_recipe_hidden(req: &HttpRequest<AppState>, hidden: bool) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    let dbref = req.state().db.clone();
+    futures::future::result(Path::<String>::extract(req))
+        .and_then(move |path| {
+            dbref
+                .send(db::admin::SetRecipeHidden {
+                    recipe: RecipeRef::parse(&path),
+                    hidden,
+                }).from_err()
+        }).and_then(|v| match v {
+            Ok(true) => Ok(HttpResponse::NoContent().finish()),
+            Ok(false) => Ok(HttpResponse::NotFound().body("No such recipe")),
+            v => json_response(v),
+        }).responder()
+}
+
+fn admin_hide_recipe(req: &HttpRequest<AppState>) -> impl Responder {
+    set_recipe_hidden(req, true)
+}
+
+fn admin_unhide_recipe(req: &HttpRequest<AppState>) -> impl Responder {
+    set_recipe_hidden(req, false)
+}
+
+fn admin_start_import(req: &HttpRequest<AppState>) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    let imports = req.state().imports.clone();
+    req.body()
+        .limit(MAX_IMPORT_SIZE)
+        .from_err::<actix_web::Error>()
+        .and_then(move |body| match imports.start(body.to_vec()) {
+            Some(job) => Ok(HttpResponse::Accepted()
+                .header(http::header::LOCATION, format!("/admin/imports/{}", job.id))
+                .json(job)),
+            None => Ok(HttpResponse::Conflict().body("Another import is still running")),
+        }).responder()
+}
+
+fn admin_import_status(req: &HttpRequest<AppState>) -> impl Responder {
+    if let Err(e) = check_admin(req) {
+        return futures::future::err::<HttpResponse, _>(e).responder();
+    }
+
+    let imports = req.state().imports.clone();
+    futures::future::result(Path::<u32>::extract(req))
+        .and_then(move |id| match imports.get(*id) {
+            Some(job) => Ok(HttpResponse::Ok().json(job)),
+            None => Ok(HttpResponse::NotFound().body("No such import")),
+        }).responder()
+}
+
 fn setup_env() {
     dotenv::dotenv().ok();
     let env = env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info");
@@ -113,12 +664,16 @@ struct ServerConfiguration {
     db_addr: actix::Addr<db::DbExecutor>,
     static_path: Option<PathBuf>,
     images_path: Option<PathBuf>,
+    admin_token: Option<String>,
+    imports: imports::ImportJobs,
 }
 
 fn start_server(listen_addr: &str, server_configuration: ServerConfiguration) {
     server::new(move || {
         let app_state = AppState {
             db: server_configuration.db_addr.clone(),
+            admin_token: server_configuration.admin_token.clone(),
+            imports: server_configuration.imports.clone(),
         };
 
         let mut app = App::with_state(app_state)
@@ -126,12 +681,55 @@ fn start_server(listen_addr: &str, server_configuration: ServerConfiguration) {
             .resource("/", |r| r.f(index))
             .resource("/producers/{id}.json", |r| {
                 r.method(http::Method::GET).f(recipes_for_item)
+            }).resource("/uses/{id}.json", |r| {
+                r.method(http::Method::GET).f(uses_for_item)
+            }).resource("/items/lookup.json", |r| {
+                r.method(http::Method::POST).f(lookup_items)
+            }).resource("/items/by-mcid/{minecraft_id}.json", |r| {
+                r.method(http::Method::GET).f(item_by_minecraft_id)
             }).resource("/items/{id}.json", |r| {
                 r.method(http::Method::GET).f(item_info)
+            }).resource("/items/{id}/variants.json", |r| {
+                r.method(http::Method::GET).f(item_variants)
             }).resource("/recipe/{id}.json", |r| {
                 r.method(http::Method::GET).f(complete_recipe)
+            }).resource("/recipes.json", |r| {
+                r.method(http::Method::GET).f(complete_recipes)
+            }).resource("/machines.json", |r| {
+                r.method(http::Method::GET).f(list_machines)
+            }).resource("/machines/{id}.json", |r| {
+                r.method(http::Method::GET).f(machine_info)
+            }).resource("/machines/{id}/recipes.json", |r| {
+                r.method(http::Method::GET).f(machine_recipes)
+            }).resource("/tree/{id}.json", |r| {
+                r.method(http::Method::GET).f(crafting_tree)
+            }).resource("/graph/{id}.{format}", |r| {
+                r.method(http::Method::GET).f(item_graph)
+            }).resource("/path.json", |r| {
+                r.method(http::Method::GET).f(crafting_paths)
             }).resource("/search.json", |r| {
                 r.method(http::Method::GET).f(search_for_item)
+            }).resource("/bom.json", |r| {
+                r.method(http::Method::POST).f(compute_bom)
+            }).resource("/plans.json", |r| {
+                r.method(http::Method::POST).f(create_plan)
+            }).resource("/plans/{slug}.json", |r| {
+                r.method(http::Method::GET).f(get_plan);
+                r.method(http::Method::PUT).f(update_plan)
+            }).resource("/admin/health.json", |r| {
+                r.method(http::Method::GET).f(admin_health)
+            }).resource("/admin/conflicts.json", |r| {
+                r.method(http::Method::GET).f(admin_conflicts)
+            }).resource("/admin/recipes.json", |r| {
+                r.method(http::Method::POST).f(admin_create_recipe)
+            }).resource("/admin/recipes/{id}/hide", |r| {
+                r.method(http::Method::POST).f(admin_hide_recipe)
+            }).resource("/admin/recipes/{id}/unhide", |r| {
+                r.method(http::Method::POST).f(admin_unhide_recipe)
+            }).resource("/admin/imports", |r| {
+                r.method(http::Method::POST).f(admin_start_import)
+            }).resource("/admin/imports/{id}", |r| {
+                r.method(http::Method::GET).f(admin_import_status)
             });
 
         if let Some(ref static_path) = server_configuration.static_path {
@@ -165,14 +763,31 @@ fn start_server(listen_addr: &str, server_configuration: ServerConfiguration) {
     )).run()
 }
 
+/// Arguments to the `graph` command
+struct GraphArgs {
+    item: i32,
+    depth: usize,
+    recipes: Vec<RecipeChoice>,
+    format: GraphFormat,
+}
+
 struct ArgsOutput {
     bind_address: String,
     static_path: Option<PathBuf>,
     images_path: Option<PathBuf>,
+    admin_token: Option<String>,
+    graph: Option<GraphArgs>,
 }
 
 fn app_args() -> ArgsOutput {
-    use clap::{App, Arg};
+    use clap::{App, Arg, SubCommand};
+
+    fn parses_as<T: std::str::FromStr>(v: String) -> Result<(), String>
+    where
+        T::Err: fmt::Display,
+    {
+        v.parse::<T>().map(|_| ()).map_err(|e| e.to_string())
+    }
 
     let matches = App::new("mccraft_web_server")
         .author("Reed Koser")
@@ -192,6 +807,38 @@ fn app_args() -> ArgsOutput {
                 .long("image-path")
                 .takes_value(true)
                 .help("Path to jeiexporter output path"),
+        ).arg(
+            Arg::with_name("admin-token")
+                .long("admin-token")
+                .takes_value(true)
+                .help("Bearer token for the admin endpoints. Defaults to $MCCRAFT_ADMIN_TOKEN"),
+        ).subcommand(
+            SubCommand::with_name("graph")
+                .about("Print the crafting graph for an item instead of starting the server")
+                .arg(
+                    Arg::with_name("item")
+                        .required(true)
+                        .validator(parses_as::<i32>)
+                        .help("ID of the item to graph"),
+                ).arg(
+                    Arg::with_name("depth")
+                        .long("depth")
+                        .default_value("3")
+                        .validator(parses_as::<usize>)
+                        .help("How many recipes deep to go"),
+                ).arg(
+                    Arg::with_name("recipe")
+                        .long("recipe")
+                        .value_name("ITEM:RECIPE")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Use a particular recipe (ID or hash) for an item"),
+                ).arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .possible_values(&["dot", "graphml"])
+                        .default_value("dot"),
+                ),
         ).get_matches();
 
     let static_path = matches
@@ -200,11 +847,55 @@ fn app_args() -> ArgsOutput {
     let images_path = matches
         .value_of("image-path")
         .map(|x| PathBuf::from(x.to_string()));
+    let admin_token = matches
+        .value_of("admin-token")
+        .map(|x| x.to_string())
+        .or_else(|| std::env::var("MCCRAFT_ADMIN_TOKEN").ok())
+        .filter(|x| !x.is_empty());
+    let graph = matches.subcommand_matches("graph").map(|graph| {
+        let recipes: Vec<&str> = graph
+            .values_of("recipe")
+            .map(|x| x.collect())
+            .unwrap_or_default();
+        GraphArgs {
+            item: graph.value_of("item").unwrap().parse().unwrap(),
+            depth: graph
+                .value_of("depth")
+                .unwrap()
+                .parse::<usize>()
+                .unwrap()
+                .min(MAX_GRAPH_DEPTH),
+            recipes: parse_recipe_choices(&recipes.join(","))
+                .unwrap_or_else(|e| clap::Error::with_description(&e, clap::ErrorKind::InvalidValue).exit()),
+            format: GraphFormat::from_name(graph.value_of("format").unwrap()).unwrap(),
+        }
+    });
 
     ArgsOutput {
         bind_address: matches.value_of("bind-address").unwrap().to_string(),
         static_path: static_path,
         images_path: images_path,
+        admin_token: admin_token,
+        graph: graph,
+    }
+}
+
+/// Print the crafting graph for an item to stdout
+fn print_graph(args: GraphArgs) {
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let db = db::DbExecutor::new(&database_url)
+        .expect(&format!("error connecting to {}", database_url));
+
+    let limits = GraphLimits {
+        depth: args.depth,
+        max_recipes_per_item: DEFAULT_RECIPES_PER_ITEM,
+    };
+    match db.expand_graph(args.item, limits, &args.recipes) {
+        Ok(expansion) => print!("{}", expansion.graph.render(args.format)),
+        Err(e) => {
+            error!("Failed to build the graph: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
@@ -213,10 +904,16 @@ fn main() {
 
     let args = app_args();
 
+    if let Some(graph) = args.graph {
+        print_graph(graph);
+        return;
+    }
+
     // the Actix system needs to be started before we run any of the actors.
     let sys = actix::System::new("mccraft-web-server");
 
     let db_addr = create_db_connection();
+    let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
 
     start_server(
         &args.bind_address,
@@ -224,6 +921,8 @@ fn main() {
             db_addr,
             static_path: args.static_path,
             images_path: args.images_path,
+            admin_token: args.admin_token,
+            imports: imports::ImportJobs::new(database_url),
         },
     );
 